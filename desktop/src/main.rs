@@ -2,8 +2,12 @@ extern crate nes_emulator;
 
 use std::collections::HashMap;
 
-use nes_emulator::cartridge::Cartridge;
+use nes_emulator::cartridge::{fds, nsf, Cartridge, CartridgeError};
 use nes_emulator::cpu::CPU;
+use nes_emulator::mapper::MapperActions;
+use nes_emulator::patch::{self, PatchFormat};
+use nes_emulator::player::NsfPlayer;
+use nes_emulator::cpu::apu::SAMPLE_RATE;
 
 use nes_emulator::cpu::ppu::joypad::ButtonStatus;
 use nes_emulator::cpu::ppu::{CYCLES_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH};
@@ -11,8 +15,11 @@ use sdl2::audio::{AudioSpecDesired, AudioCallback};
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::event::Event;
+use sdl2::messagebox::{show_simple_message_box, MessageBoxFlag};
 
 use std::{env, fs};
+use std::path::Path;
+use std::time::Duration;
 
 const FRAMES_PER_SAVE: u8 = 180;
 
@@ -45,19 +52,184 @@ impl AudioCallback for NesAudioCallback<'_> {
   }
 }
 
+fn exit_with_message(message: &str) -> ! {
+  eprintln!("{message}");
+
+  let _ = show_simple_message_box(MessageBoxFlag::ERROR, "NES Emulator", message, None);
+
+  std::process::exit(1);
+}
+
+// picks up a patch sitting next to the rom with the same name, e.g. game.nes + game.bps
+fn apply_soft_patch(filepath: &str, rom: Vec<u8>) -> Vec<u8> {
+  for format in [PatchFormat::Bps, PatchFormat::Ups, PatchFormat::Ips] {
+    let patch_path = Path::new(filepath).with_extension(format.extension());
+
+    if let Ok(patch) = fs::read(&patch_path) {
+      match patch::apply(&rom, &patch) {
+        Ok(patched) => return patched,
+        Err(error) => {
+          let message = format!("Could not apply {}: {error}", patch_path.display());
+
+          eprintln!("{message}");
+          let _ = show_simple_message_box(MessageBoxFlag::WARNING, "NES Emulator", &message, None);
+        }
+      }
+    }
+  }
+
+  rom
+}
+
+// disk images need the RAM adapter BIOS, taken from NES_FDS_BIOS or a disksys.rom next to the disk
+fn load_fds(filepath: &str, image: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+  let bios_path = env::var("NES_FDS_BIOS")
+    .map(|path| Path::new(&path).to_path_buf())
+    .unwrap_or_else(|_| Path::new(filepath).with_file_name("disksys.rom"));
+
+  let bios = fs::read(&bios_path).map_err(|_| CartridgeError::FdsBiosRequired)?;
+
+  Cartridge::new_fds(image, bios, Some(filepath.to_string()))
+}
+
+fn switch_disk_side(cpu: &mut CPU) {
+  let sides = cpu.ppu.mapper.disk_sides();
+
+  if sides == 0 {
+    return;
+  }
+
+  let next_side = cpu.ppu.mapper.current_disk_side().map_or(0, |side| (side + 1) % sides);
+
+  cpu.ppu.mapper.insert_disk(next_side);
+}
+
+fn nsf_window_title(player: &NsfPlayer) -> String {
+  let track = player.current_track();
+
+  let name = player.track_info(track)
+    .and_then(|info| info.name.clone())
+    .unwrap_or_else(|| player.title().to_string());
+
+  format!("{name} - {} ({}/{})", player.artist(), track + 1, player.track_count())
+}
+
+// NSF rips have no picture, so the window only shows what's playing. left and right change tracks
+fn play_nsf(filepath: &str, bytes: Vec<u8>) -> ! {
+  let mut player = match NsfPlayer::new(&bytes) {
+    Ok(player) => player,
+    Err(error) => exit_with_message(&format!("Could not load {filepath}: {error}"))
+  };
+
+  let sdl_context = sdl2::init().unwrap();
+  let video_subsystem = sdl_context.video().unwrap();
+  let audio_subsystem = sdl_context.audio().unwrap();
+
+  let spec = AudioSpecDesired {
+    freq: Some(SAMPLE_RATE as i32),
+    channels: Some(1),
+    samples: Some(4096)
+  };
+
+  let queue = audio_subsystem.open_queue::<f32, _>(None, &spec).unwrap();
+
+  queue.resume();
+
+  let mut window = video_subsystem
+    .window(&nsf_window_title(&player), 480, 120)
+    .position_centered()
+    .build()
+    .unwrap();
+
+  let mut event_pump = sdl_context.event_pump().unwrap();
+
+  let mut samples = vec![0.0; SAMPLE_RATE / 60];
+  let mut current_track = player.current_track();
+
+  loop {
+    for event in event_pump.poll_iter() {
+      match event {
+        Event::Quit { .. }
+        | Event::KeyDown {
+            keycode: Some(Keycode::Escape),
+            ..
+        } => std::process::exit(0),
+        Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
+          player.select_track(player.current_track().wrapping_add(1));
+          queue.clear();
+        }
+        Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
+          player.select_track(player.current_track().checked_sub(1).unwrap_or(player.track_count().max(1) - 1));
+          queue.clear();
+        }
+        _ => { /* do nothing */ }
+      }
+    }
+
+    if player.is_track_finished() {
+      player.select_track(player.current_track().wrapping_add(1));
+    }
+
+    if player.current_track() != current_track {
+      current_track = player.current_track();
+
+      let _ = window.set_title(&nsf_window_title(&player));
+    }
+
+    // keep roughly a few frames of audio queued
+    if queue.size() < (samples.len() * 4 * std::mem::size_of::<f32>()) as u32 {
+      player.render(&mut samples);
+
+      for sample in samples.iter_mut() {
+        *sample *= 0.5;
+      }
+
+      let _ = queue.queue_audio(&samples);
+    } else {
+      std::thread::sleep(Duration::from_millis(1));
+    }
+  }
+}
+
 fn main() {
   let args: Vec<String> = env::args().collect();
 
-  if args.len() != 2 {
-    panic!("Please specify a filename.");
+  if args.len() < 2 || args.len() > 3 {
+    panic!("Please specify a filename and optionally a save file path.");
   }
 
   let filepath = &args[1];
 
-  let bytes: Vec<u8> = fs::read(filepath).unwrap();
+  let bytes: Vec<u8> = match fs::read(filepath) {
+    Ok(bytes) => bytes,
+    Err(error) => exit_with_message(&format!("Could not read {filepath}: {error}"))
+  };
+
+  // a disk image's .ips holds its saved writes, which the FDS mapper applies itself
+  let bytes = if fds::is_fds_image(&bytes) { bytes } else { apply_soft_patch(filepath, bytes) };
+
+  if nsf::is_nsf(&bytes) {
+    play_nsf(filepath, bytes);
+  }
+
+  let cartridge = if fds::is_fds_image(&bytes) {
+    load_fds(filepath, bytes)
+  } else {
+    Cartridge::new(bytes, Some(filepath.to_string()))
+  };
+
+  let mut cartridge = match cartridge {
+    Ok(cartridge) => cartridge,
+    Err(error) => exit_with_message(&format!("Could not load {filepath}: {error}"))
+  };
+
+  if let Some(save_path) = args.get(2) {
+    cartridge.save_path = Some(save_path.to_string());
+  }
+
   let mut cpu = CPU::new();
 
-  cpu.load_game(Cartridge::new(bytes, Some(filepath.to_string())));
+  cpu.load_game(cartridge);
 
   let sdl_context = sdl2::init().unwrap();
   let video_subsystem = sdl_context.video().unwrap();
@@ -172,6 +344,9 @@ fn main() {
             keycode: Some(Keycode::Escape),
             ..
         } => std::process::exit(0),
+        Event::KeyDown { keycode: Some(Keycode::F1), .. } => switch_disk_side(&mut cpu),
+        Event::KeyDown { keycode: Some(Keycode::F2), .. } => cpu.ppu.mapper.eject_disk(),
+        Event::KeyDown { keycode: Some(Keycode::F3), .. } => cpu.reset(),
         Event::KeyDown { keycode, .. }=> {
           if let Some(button) = key_map.get(&keycode.unwrap_or(Keycode::Return)){
             cpu.ppu.joypad.set_button(*button, true);
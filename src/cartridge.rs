@@ -1,14 +1,29 @@
-const PRG_ROM_MULTIPLIER: usize = 16384;
-const CHR_ROM_MULTIPLIER: usize = 8192;
+pub mod header;
+pub mod error;
+pub mod checksum;
+pub mod database;
+pub mod unif;
+pub mod fds;
+pub mod nsf;
 
 const NES_ASCII: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
 
-use crate::mapper::{Mapper, sxrom::Sxrom, Empty, uxrom::Uxrom, cnrom::Cnrom, txrom::Txrom};
+const DEFAULT_CHR_RAM_SIZE: usize = 8192;
+
+use crate::mapper::{Mapper, MapperRegistry, Empty, fds::Fds, nsf::Nsf};
+use std::path::Path;
+
+use header::{Header, TimingRegion, HEADER_SIZE, TRAINER_SIZE};
+use nsf::NsfFile;
+use database::{GameDatabase, HeaderCorrection};
+use unif::UNIF_ASCII;
+pub use error::CartridgeError;
 use strum_macros::Display;
 
 
-#[derive(Copy, Clone, Display)]
+#[derive(Copy, Clone, Display, Debug, Default, PartialEq, Eq)]
 pub enum Mirroring {
+  #[default]
   Horizontal,
   Vertical,
   SingleScreenA,
@@ -21,68 +36,235 @@ pub struct Cartridge {
   pub prg_ram: Vec<u8>,
   pub chr_rom: Vec<u8>,
   pub chr_ram: Vec<u8>,
+  // nametable RAM on the board beyond the console's own 2K, handed out as Nametable::CartridgeVram pages
+  pub vram: Vec<u8>,
   pub mirroring: Mirroring,
   pub mapper: Mapper,
-  pub path: Option<String>
+  pub header: Header,
+  pub trainer: Option<Vec<u8>>,
+  pub crc32: u32,
+  pub sha1: [u8; 20],
+  pub corrections: Vec<HeaderCorrection>,
+  pub path: Option<String>,
+  pub save_path: Option<String>
 }
 
 impl Cartridge {
-  pub fn new(rom: Vec<u8>, path: Option<String>) -> Self {
-    let prg_len: usize = rom[4] as usize * PRG_ROM_MULTIPLIER;
-    let chr_len: usize = rom[5] as usize * CHR_ROM_MULTIPLIER;
+  pub fn new(rom: Vec<u8>, path: Option<String>) -> Result<Self, CartridgeError> {
+    Self::parse(rom, path, GameDatabase::embedded(), MapperRegistry::builtin())
+  }
+
+  pub fn new_with_database(rom: Vec<u8>, path: Option<String>, database: &GameDatabase) -> Result<Self, CartridgeError> {
+    Self::parse(rom, path, database, MapperRegistry::builtin())
+  }
+
+  // for boards that aren't built in, see MapperRegistry
+  pub fn new_with_registry(rom: Vec<u8>, path: Option<String>, registry: &MapperRegistry) -> Result<Self, CartridgeError> {
+    Self::parse(rom, path, GameDatabase::embedded(), registry)
+  }
 
-    if rom[0..4] != NES_ASCII {
-      panic!("file is not in ines format!");
+  fn parse(rom: Vec<u8>, path: Option<String>, database: &GameDatabase, registry: &MapperRegistry) -> Result<Self, CartridgeError> {
+    if fds::is_fds_image(&rom) {
+      return Err(CartridgeError::FdsBiosRequired);
     }
 
-    let ines_ver = (rom[7] >> 2) & 0b11;
-    if ines_ver != 0 {
-        panic!("NES2.0 format is not supported");
+    if nsf::is_nsf(&rom) {
+      return Err(CartridgeError::NsfNotCartridge);
     }
 
-    let mapper_number = ((rom[7] >> 4) & 0b1111) << 4 | (rom[6] >> 4 & 0b1111);
+    if rom.starts_with(&UNIF_ASCII) {
+      let image = unif::parse(&rom)?;
 
-    let four_screen: bool = rom[6] & 0b1000 != 0;
-    let vertical_mirroring = rom[6] & 0b1 != 0;
+      return Self::build(image.header, image.prg_rom, image.chr_rom, None, path, database, registry);
+    }
 
-    let screen_mirroring = if four_screen {
-      Mirroring::FourScreen
-    } else if vertical_mirroring {
-      Mirroring::Vertical
+    let header = Header::parse(&rom)?;
+
+    let prg_rom_start = header.prg_rom_start();
+    let chr_rom_start = header.chr_rom_start();
+
+    let prg_available = rom.len().saturating_sub(prg_rom_start);
+    if prg_available < header.prg_rom_size || rom.len() < prg_rom_start {
+      return Err(CartridgeError::TruncatedPrg { expected: header.prg_rom_size, actual: prg_available });
+    }
+
+    let chr_available = rom.len().saturating_sub(chr_rom_start);
+    if chr_available < header.chr_rom_size {
+      return Err(CartridgeError::TruncatedChr { expected: header.chr_rom_size, actual: chr_available });
+    }
+
+    // the trainer sits right after the header, before PRG-ROM
+    let trainer = if header.has_trainer {
+      Some(rom[HEADER_SIZE .. (HEADER_SIZE + TRAINER_SIZE)].to_vec())
     } else {
-      Mirroring::Horizontal
+      None
     };
 
-    let skip_trainer = (rom[6] >> 3) & 0b1 == 1;
+    let prg_rom = rom[prg_rom_start .. (prg_rom_start + header.prg_rom_size)].to_vec();
+    let chr_rom = rom[chr_rom_start .. (chr_rom_start + header.chr_rom_size)].to_vec();
+
+    Self::build(header, prg_rom, chr_rom, trainer, path, database, registry)
+  }
+
+  // disk images need the RAM adapter's BIOS, which is loaded as the cartridge's PRG-ROM
+  pub fn new_fds(image: Vec<u8>, bios: Vec<u8>, path: Option<String>) -> Result<Self, CartridgeError> {
+    if bios.len() != fds::BIOS_SIZE {
+      return Err(CartridgeError::InvalidFdsBios { size: bios.len() });
+    }
+
+    let sides = fds::parse(&image)?;
 
-    let prg_rom_start = 16 + if skip_trainer { 512 } else { 0 };
-    let chr_rom_start = prg_rom_start + prg_len;
+    if sides.is_empty() {
+      return Err(CartridgeError::UnsupportedFormat("disk image has no sides".to_string()));
+    }
 
-    let mut chr_ram: Vec<u8> = Vec::new();
-    let prg_ram: Vec<u8> = Vec::new();
+    // FDS is reserved as mapper 20, and the RAM adapter has a battery for our purposes
+    // since writes to the disk need to stick around
+    let header = Header {
+      mapper: 20,
+      prg_rom_size: bios.len(),
+      chr_ram_size: 8192,
+      has_battery: true,
+      ..Default::default()
+    };
 
-    chr_ram.resize(8192, 0);
+    // disk writes go to an IPS patch next to the image rather than a .sav
+    let save_path = path.as_ref().map(|path| Path::new(path).with_extension("ips").to_string_lossy().into_owned());
 
     let mut cartridge = Cartridge {
-      prg_rom: rom[prg_rom_start .. (prg_rom_start + prg_len)].to_vec(),
-      chr_rom: rom[chr_rom_start .. (chr_rom_start + chr_len)].to_vec(),
-      mirroring: screen_mirroring,
-      chr_ram,
-      prg_ram,
-      mapper: Mapper::Empty(Empty {}),
-      path
+      prg_rom: bios,
+      chr_rom: Vec::new(),
+      mirroring: header.mirroring,
+      chr_ram: vec![0; 8192],
+      vram: Vec::new(),
+      prg_ram: Vec::new(),
+      mapper: Box::new(Empty::default()),
+      header,
+      trainer: None,
+      crc32: checksum::crc32(fds::strip_header(&image)),
+      sha1: checksum::sha1(fds::strip_header(&image)),
+      corrections: Vec::new(),
+      path,
+      save_path
     };
 
-    cartridge.mapper = match mapper_number {
-      0 => Mapper::Empty(Empty {}),
-      1 => Mapper::Sxrom(Sxrom::load(&mut cartridge, false)),
-      2 => Mapper::Uxrom(Uxrom::load(&mut cartridge)),
-      3 => Mapper::Cnrom(Cnrom::load(&mut cartridge)),
-      4 => Mapper::Txrom(Txrom::load(&mut cartridge)),
-      155 => Mapper::Sxrom(Sxrom::load(&mut cartridge, true)),
-      _ => panic!("unsupported mapper: {mapper_number}")
+    cartridge.mapper = Box::new(Fds::load(&mut cartridge, &image));
+
+    Ok(cartridge)
+  }
+
+  // NSF rips have no cartridge of their own, so they get the NSF virtual board
+  pub fn new_nsf(nsf: &NsfFile) -> Self {
+    let header = Header {
+      prg_ram_size: 8192,
+      chr_ram_size: 8192,
+      timing: if nsf.is_pal { TimingRegion::Pal } else { TimingRegion::Ntsc },
+      ..Default::default()
     };
 
+    let mut cartridge = Cartridge {
+      prg_rom: Vec::new(),
+      chr_rom: Vec::new(),
+      mirroring: header.mirroring,
+      chr_ram: vec![0; 8192],
+      vram: Vec::new(),
+      prg_ram: vec![0; 8192],
+      mapper: Box::new(Empty::default()),
+      header,
+      trainer: None,
+      crc32: checksum::crc32(&nsf.data),
+      sha1: checksum::sha1(&nsf.data),
+      corrections: Vec::new(),
+      path: None,
+      save_path: None
+    };
+
+    cartridge.mapper = Box::new(Nsf::load(&mut cartridge, nsf));
+
     cartridge
   }
-}
\ No newline at end of file
+
+  fn build(
+    mut header: Header,
+    prg_rom: Vec<u8>,
+    chr_rom: Vec<u8>,
+    trainer: Option<Vec<u8>>,
+    path: Option<String>,
+    database: &GameDatabase,
+    registry: &MapperRegistry
+  ) -> Result<Self, CartridgeError> {
+    let crc32 = checksum::crc32_update(checksum::crc32(&prg_rom), &chr_rom);
+    let sha1 = checksum::sha1(&[prg_rom.as_slice(), chr_rom.as_slice()].concat());
+
+    let corrections = match database.lookup(crc32, &sha1) {
+      Some(game) => game.apply(&mut header),
+      None => Vec::new()
+    };
+
+    let prg_ram: Vec<u8> = vec![0; header.prg_ram_size + header.prg_nvram_size];
+
+    // boards without CHR-ROM need somewhere to draw from even if the header says 0
+    let chr_ram_size = match header.chr_ram_size + header.chr_nvram_size {
+      0 if chr_rom.is_empty() => DEFAULT_CHR_RAM_SIZE,
+      size => size
+    };
+
+    let chr_ram: Vec<u8> = vec![0; chr_ram_size];
+
+    // four-screen boards carry another 2K so all four nametables are distinct
+    let vram: Vec<u8> = if header.mirroring == Mirroring::FourScreen { vec![0; 2048] } else { Vec::new() };
+
+    let save_path = path.as_ref().map(|path| Path::new(path).with_extension("sav").to_string_lossy().into_owned());
+
+    let mut cartridge = Cartridge {
+      prg_rom,
+      chr_rom,
+      mirroring: header.mirroring,
+      chr_ram,
+      vram,
+      prg_ram,
+      mapper: Box::new(Empty::default()),
+      header,
+      trainer,
+      crc32,
+      sha1,
+      corrections,
+      path,
+      save_path
+    };
+
+    let mapper_number = cartridge.header.mapper;
+
+    let factory = registry.factory(mapper_number, cartridge.header.submapper)
+      .ok_or(CartridgeError::UnsupportedMapper(mapper_number))?;
+
+    cartridge.mapper = factory(&mut cartridge);
+
+    Ok(cartridge)
+  }
+
+  pub fn has_battery(&self) -> bool {
+    self.header.has_battery
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Cartridge;
+
+  fn nes2_image(mapper: u8, prg_16k_banks: u8, chr_8k_banks: u8, chr_ram_shift: u8) -> Vec<u8> {
+    let mut rom = vec![0x4e, 0x45, 0x53, 0x1a, prg_16k_banks, chr_8k_banks, mapper << 4, 0x08, 0, 0, 0, chr_ram_shift];
+
+    rom.resize(16 + prg_16k_banks as usize * 16_384 + chr_8k_banks as usize * 8192, 0);
+    rom
+  }
+
+  #[test]
+  fn sizes_chr_ram_from_the_header() {
+    assert_eq!(Cartridge::new(nes2_image(2, 8, 0, 9), None).unwrap().chr_ram.len(), 32_768);
+    assert_eq!(Cartridge::new(nes2_image(0, 2, 1, 0), None).unwrap().chr_ram.len(), 0);
+
+    // nothing to draw from otherwise
+    assert_eq!(Cartridge::new(nes2_image(0, 2, 0, 0), None).unwrap().chr_ram.len(), 8192);
+  }
+}
@@ -0,0 +1,127 @@
+// CRC-32 (IEEE 802.3, as used by zip and the NES 2.0 database)
+const CRC32_POLYNOMIAL: u32 = 0xedb88320;
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+  let mut table = [0; 256];
+  let mut i = 0;
+
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut bit = 0;
+
+    while bit < 8 {
+      crc = if crc & 0b1 == 1 { (crc >> 1) ^ CRC32_POLYNOMIAL } else { crc >> 1 };
+      bit += 1;
+    }
+
+    table[i] = crc;
+    i += 1;
+  }
+
+  table
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+  crc32_update(0, data)
+}
+
+// continues a running checksum, so PRG and CHR can be hashed without joining them first
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+  let mut crc = !crc;
+
+  for byte in data {
+    crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+  }
+
+  !crc
+}
+
+// see https://en.wikipedia.org/wiki/SHA-1#SHA-1_pseudocode
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+  let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+
+  let mut message = data.to_vec();
+  let bit_length = (data.len() as u64).wrapping_mul(8);
+
+  message.push(0x80);
+  while message.len() % 64 != 56 {
+    message.push(0);
+  }
+  message.extend_from_slice(&bit_length.to_be_bytes());
+
+  for chunk in message.chunks(64) {
+    let mut w = [0u32; 80];
+
+    for (i, word) in chunk.chunks(4).enumerate() {
+      w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..80 {
+      w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = h;
+
+    for (i, word) in w.iter().enumerate() {
+      let (f, k) = match i {
+        0..=19 => ((b & c) | (!b & d), 0x5a827999),
+        20..=39 => (b ^ c ^ d, 0x6ed9eba1),
+        40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+        _ => (b ^ c ^ d, 0xca62c1d6)
+      };
+
+      let temp = a.rotate_left(5)
+        .wrapping_add(f)
+        .wrapping_add(e)
+        .wrapping_add(k)
+        .wrapping_add(*word);
+
+      e = d;
+      d = c;
+      c = b.rotate_left(30);
+      b = a;
+      a = temp;
+    }
+
+    h[0] = h[0].wrapping_add(a);
+    h[1] = h[1].wrapping_add(b);
+    h[2] = h[2].wrapping_add(c);
+    h[3] = h[3].wrapping_add(d);
+    h[4] = h[4].wrapping_add(e);
+  }
+
+  let mut digest = [0; 20];
+
+  for (i, word) in h.iter().enumerate() {
+    digest[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+  }
+
+  digest
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{crc32, crc32_update, sha1};
+
+  fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+  }
+
+  #[test]
+  fn crc32_known_answers() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xcbf43926);
+  }
+
+  #[test]
+  fn sha1_known_answers() {
+    assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(hex(&sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(
+      hex(&sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+      "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+  }
+}
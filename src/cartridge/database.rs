@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use super::Mirroring;
+use super::header::{Header, TimingRegion};
+
+// the embedded database uses the same layout as the NES 2.0 XML database
+// (https://forums.nesdev.org/viewtopic.php?t=19940), so newer dumps of it can
+// be dropped in as-is or trimmed down to the games that need fixing
+const EMBEDDED_DATABASE: &str = include_str!("nes20db.xml");
+
+lazy_static! {
+  static ref EMBEDDED: GameDatabase = GameDatabase::parse(EMBEDDED_DATABASE);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GameEntry {
+  pub name: Option<String>,
+  pub crc32: u32,
+  pub sha1: Option<[u8; 20]>,
+  pub mapper: Option<u16>,
+  pub submapper: Option<u8>,
+  pub mirroring: Option<Mirroring>,
+  pub has_battery: Option<bool>,
+  pub prg_ram_size: Option<usize>,
+  pub prg_nvram_size: Option<usize>,
+  pub chr_ram_size: Option<usize>,
+  pub chr_nvram_size: Option<usize>,
+  pub timing: Option<TimingRegion>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HeaderCorrection {
+  Mapper { from: u16, to: u16 },
+  Submapper { from: u8, to: u8 },
+  Mirroring { from: Mirroring, to: Mirroring },
+  Battery { from: bool, to: bool },
+  PrgRamSize { from: usize, to: usize },
+  PrgNvramSize { from: usize, to: usize },
+  ChrRamSize { from: usize, to: usize },
+  ChrNvramSize { from: usize, to: usize },
+  Timing { from: TimingRegion, to: TimingRegion }
+}
+
+#[derive(Default)]
+pub struct GameDatabase {
+  games: HashMap<u32, Vec<GameEntry>>
+}
+
+impl GameDatabase {
+  pub fn embedded() -> &'static GameDatabase {
+    &EMBEDDED
+  }
+
+  pub fn parse(xml: &str) -> Self {
+    let mut database = GameDatabase::default();
+    let mut current: Option<GameEntry> = None;
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+      rest = &rest[start..];
+
+      if rest.starts_with("<!--") {
+        let (comment, end) = match rest.find("-->") {
+          Some(end) => (&rest[4..end], end + 3),
+          None => (&rest[4..], rest.len())
+        };
+
+        // nes20db puts the game's name in a comment as the first child of <game>
+        if let Some(game) = current.as_mut() {
+          if game.name.is_none() {
+            game.name = Some(comment.trim().to_string());
+          }
+        }
+
+        rest = &rest[end..];
+        continue;
+      }
+
+      let end = match rest.find('>') {
+        Some(end) => end,
+        None => break
+      };
+
+      let tag = &rest[1..end];
+      rest = &rest[end + 1..];
+
+      let name = tag.split_whitespace().next().unwrap_or("");
+
+      match name {
+        "game" => current = Some(GameEntry::default()),
+        "/game" => {
+          if let Some(game) = current.take() {
+            database.insert(game);
+          }
+        }
+        _ => {
+          if let Some(game) = current.as_mut() {
+            Self::parse_element(game, name, tag);
+          }
+        }
+      }
+    }
+
+    database
+  }
+
+  fn parse_element(game: &mut GameEntry, name: &str, tag: &str) {
+    let attribute = |key: &str| Self::attribute(tag, key);
+    let size = |key: &str| attribute(key).and_then(|size| size.parse::<usize>().ok());
+
+    match name {
+      // checksums of PRG and CHR together, which is what we look games up by
+      "rom" => {
+        if let Some(crc32) = attribute("crc32").and_then(|crc| u32::from_str_radix(crc, 16).ok()) {
+          game.crc32 = crc32;
+        }
+        game.sha1 = attribute("sha1").and_then(Self::parse_sha1);
+      }
+      "prgram" => game.prg_ram_size = size("size"),
+      "prgnvram" => game.prg_nvram_size = size("size"),
+      "chrram" => game.chr_ram_size = size("size"),
+      "chrnvram" => game.chr_nvram_size = size("size"),
+      "pcb" => {
+        game.mapper = attribute("mapper").and_then(|mapper| mapper.parse().ok());
+        game.submapper = attribute("submapper").and_then(|submapper| submapper.parse().ok());
+        game.has_battery = attribute("battery").map(|battery| battery == "1");
+        game.mirroring = match attribute("mirroring") {
+          Some("H") => Some(Mirroring::Horizontal),
+          Some("V") => Some(Mirroring::Vertical),
+          Some("4") => Some(Mirroring::FourScreen),
+          _ => None
+        };
+      }
+      "console" => {
+        game.timing = match attribute("region") {
+          Some("0") => Some(TimingRegion::Ntsc),
+          Some("1") => Some(TimingRegion::Pal),
+          Some("2") => Some(TimingRegion::MultiRegion),
+          Some("3") => Some(TimingRegion::Dendy),
+          _ => None
+        };
+      }
+      _ => ()
+    }
+  }
+
+  fn attribute<'a>(tag: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("{key}=\"");
+    let mut search = tag;
+
+    while let Some(index) = search.find(&pattern) {
+      // make sure we matched a whole attribute name and not the tail of a longer one
+      let preceded_by_space = index == 0 || search[..index].ends_with(char::is_whitespace);
+      let value_start = index + pattern.len();
+
+      if preceded_by_space {
+        let value = &search[value_start..];
+
+        return value.find('"').map(|end| &value[..end]);
+      }
+
+      search = &search[value_start..];
+    }
+
+    None
+  }
+
+  fn parse_sha1(sha1: &str) -> Option<[u8; 20]> {
+    if sha1.len() != 40 {
+      return None;
+    }
+
+    let mut digest = [0; 20];
+
+    for (i, byte) in digest.iter_mut().enumerate() {
+      *byte = u8::from_str_radix(&sha1[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(digest)
+  }
+
+  pub fn insert(&mut self, game: GameEntry) {
+    self.games.entry(game.crc32).or_default().push(game);
+  }
+
+  pub fn len(&self) -> usize {
+    self.games.values().map(|games| games.len()).sum()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.games.is_empty()
+  }
+
+  // CRC32 collisions are possible across a database this size, so when the entry
+  // has a SHA-1 it has to agree as well
+  pub fn lookup(&self, crc32: u32, sha1: &[u8; 20]) -> Option<&GameEntry> {
+    self.games.get(&crc32)?
+      .iter()
+      .find(|game| game.sha1.is_none_or(|game_sha1| game_sha1 == *sha1))
+  }
+}
+
+impl GameEntry {
+  pub fn apply(&self, header: &mut Header) -> Vec<HeaderCorrection> {
+    let mut corrections = Vec::new();
+
+    if let Some(mapper) = self.mapper {
+      if mapper != header.mapper {
+        corrections.push(HeaderCorrection::Mapper { from: header.mapper, to: mapper });
+        header.mapper = mapper;
+      }
+    }
+    if let Some(submapper) = self.submapper {
+      if submapper != header.submapper {
+        corrections.push(HeaderCorrection::Submapper { from: header.submapper, to: submapper });
+        header.submapper = submapper;
+      }
+    }
+    if let Some(mirroring) = self.mirroring {
+      if mirroring != header.mirroring {
+        corrections.push(HeaderCorrection::Mirroring { from: header.mirroring, to: mirroring });
+        header.mirroring = mirroring;
+      }
+    }
+    if let Some(has_battery) = self.has_battery {
+      if has_battery != header.has_battery {
+        corrections.push(HeaderCorrection::Battery { from: header.has_battery, to: has_battery });
+        header.has_battery = has_battery;
+      }
+    }
+
+    // the database only lists the RAM a board actually has, so a missing element means none
+    Self::correct_size(&mut header.prg_ram_size, self.prg_ram_size, &mut corrections, |from, to| HeaderCorrection::PrgRamSize { from, to });
+    Self::correct_size(&mut header.prg_nvram_size, self.prg_nvram_size, &mut corrections, |from, to| HeaderCorrection::PrgNvramSize { from, to });
+    Self::correct_size(&mut header.chr_ram_size, self.chr_ram_size, &mut corrections, |from, to| HeaderCorrection::ChrRamSize { from, to });
+    Self::correct_size(&mut header.chr_nvram_size, self.chr_nvram_size, &mut corrections, |from, to| HeaderCorrection::ChrNvramSize { from, to });
+
+    if let Some(timing) = self.timing {
+      if timing != header.timing {
+        corrections.push(HeaderCorrection::Timing { from: header.timing, to: timing });
+        header.timing = timing;
+      }
+    }
+
+    corrections
+  }
+
+  fn correct_size(
+    header_size: &mut usize,
+    size: Option<usize>,
+    corrections: &mut Vec<HeaderCorrection>,
+    correction: fn(usize, usize) -> HeaderCorrection
+  ) {
+    let size = size.unwrap_or(0);
+
+    if size != *header_size {
+      corrections.push(correction(*header_size, size));
+      *header_size = size;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{GameDatabase, HeaderCorrection};
+  use crate::cartridge::{checksum, Cartridge, Mirroring};
+
+  // an iNES 1.0 MMC3 image without a battery, as StarTropics is often dumped
+  fn ines_image() -> Vec<u8> {
+    let mut rom = vec![0x4e, 0x45, 0x53, 0x1a, 8, 16, 0x40, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    rom.extend((0..8 * 16_384 + 16 * 8192).map(|i: usize| (i / 1024) as u8));
+    rom
+  }
+
+  fn database_for(rom: &[u8], sha1: [u8; 20]) -> GameDatabase {
+    let crc32 = checksum::crc32(&rom[16..]);
+    let sha1: String = sha1.iter().map(|byte| format!("{byte:02x}")).collect();
+
+    GameDatabase::parse(&format!(r#"
+      <nes20db>
+        <game>
+          <!-- StarTropics (USA) -->
+          <rom size="262144" crc32="{crc32:08X}" sha1="{sha1}"/>
+          <prgnvram size="1024"/>
+          <pcb mapper="4" submapper="1" mirroring="V" battery="1"/>
+          <console type="0" region="0"/>
+        </game>
+      </nes20db>
+    "#))
+  }
+
+  #[test]
+  fn corrects_a_known_dump() {
+    let rom = ines_image();
+    let database = database_for(&rom, checksum::sha1(&rom[16..]));
+
+    assert_eq!(database.len(), 1);
+
+    let cartridge = Cartridge::new_with_database(rom, None, &database).unwrap();
+
+    assert_eq!(cartridge.header.mapper, 4);
+    assert_eq!(cartridge.header.submapper, 1);
+    assert_eq!(cartridge.header.mirroring, Mirroring::Vertical);
+    assert!(cartridge.header.has_battery);
+    assert_eq!(cartridge.header.prg_ram_size, 0);
+    assert_eq!(cartridge.header.prg_nvram_size, 1024);
+    assert_eq!(cartridge.corrections, vec![
+      HeaderCorrection::Submapper { from: 0, to: 1 },
+      HeaderCorrection::Mirroring { from: Mirroring::Horizontal, to: Mirroring::Vertical },
+      HeaderCorrection::Battery { from: false, to: true },
+      HeaderCorrection::PrgRamSize { from: 8192, to: 0 },
+      HeaderCorrection::PrgNvramSize { from: 0, to: 1024 }
+    ]);
+
+    // submapper 1 is the MMC6, whose RAM starts out disabled
+    let mut mapper = cartridge.mapper;
+    assert_eq!(mapper.mem_read(0x7000), None);
+  }
+
+  #[test]
+  fn ignores_a_crc32_match_with_another_sha1() {
+    let rom = ines_image();
+    let database = database_for(&rom, [0; 20]);
+
+    let cartridge = Cartridge::new_with_database(rom, None, &database).unwrap();
+
+    assert_eq!(cartridge.header.submapper, 0);
+    assert!(cartridge.corrections.is_empty());
+  }
+}
@@ -0,0 +1,37 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CartridgeError {
+  BadMagic,
+  TruncatedHeader,
+  TruncatedPrg { expected: usize, actual: usize },
+  TruncatedChr { expected: usize, actual: usize },
+  TruncatedChunk(String),
+  UnsupportedMapper(u16),
+  UnsupportedBoard(String),
+  UnsupportedFormat(String),
+  FdsBiosRequired,
+  InvalidFdsBios { size: usize },
+  NsfNotCartridge
+}
+
+impl fmt::Display for CartridgeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CartridgeError::BadMagic => write!(f, "file is not an iNES, NES 2.0 or UNIF image"),
+      CartridgeError::TruncatedHeader => write!(f, "file is too short to contain a header"),
+      CartridgeError::TruncatedPrg { expected, actual } => write!(f, "PRG-ROM is truncated: expected {expected} bytes, found {actual}"),
+      CartridgeError::TruncatedChr { expected, actual } => write!(f, "CHR-ROM is truncated: expected {expected} bytes, found {actual}"),
+      CartridgeError::TruncatedChunk(chunk) => write!(f, "{chunk} chunk runs past the end of the file"),
+      CartridgeError::UnsupportedMapper(mapper) => write!(f, "unsupported mapper: {mapper}"),
+      CartridgeError::UnsupportedBoard(board) => write!(f, "unsupported board: {board}"),
+      CartridgeError::UnsupportedFormat(format) => write!(f, "unsupported format: {format}"),
+      CartridgeError::FdsBiosRequired => write!(f, "disk images need an FDS BIOS to run"),
+      CartridgeError::InvalidFdsBios { size } => write!(f, "FDS BIOS should be 8192 bytes, found {size}"),
+      CartridgeError::NsfNotCartridge => write!(f, "NSF files are music rips and have to be loaded in the NSF player")
+    }
+  }
+}
+
+impl Error for CartridgeError { }
@@ -0,0 +1,227 @@
+use super::CartridgeError;
+
+// see https://www.nesdev.org/wiki/FDS_file_format
+pub const FDS_ASCII: [u8; 4] = [0x46, 0x44, 0x53, 0x1a];
+pub const DISK_VERIFICATION: &[u8] = b"\x01*NINTENDO-HVC*";
+
+pub const FWNES_HEADER_SIZE: usize = 16;
+pub const DISK_SIDE_SIZE: usize = 65500;
+pub const BIOS_SIZE: usize = 8192;
+
+const DISK_HEADER_BLOCK: u8 = 1;
+const FILE_COUNT_BLOCK: u8 = 2;
+const FILE_HEADER_BLOCK: u8 = 3;
+const FILE_DATA_BLOCK: u8 = 4;
+
+const BLOCK_START_MARK: u8 = 0x80;
+
+// the drive sees a 28300 bit gap before the first block and 976 bits between blocks,
+// neither of which are stored in .fds images
+const LEADING_GAP_SIZE: usize = 28300 / 8;
+const BLOCK_GAP_SIZE: usize = 976 / 8;
+
+// .fds images don't store the block CRCs either. the BIOS only checks the CRC
+// status bit, which we never set, so any value works here
+const FAKE_CRC: [u8; 2] = [0x4d, 0x62];
+
+pub fn is_fds_image(image: &[u8]) -> bool {
+  image.starts_with(&FDS_ASCII) || image.starts_with(DISK_VERIFICATION)
+}
+
+// splits an image into its disk sides, still in the gapless .fds layout
+pub fn parse(image: &[u8]) -> Result<Vec<Vec<u8>>, CartridgeError> {
+  let data = if image.starts_with(&FDS_ASCII) {
+    if image.len() < FWNES_HEADER_SIZE {
+      return Err(CartridgeError::TruncatedHeader);
+    }
+
+    &image[FWNES_HEADER_SIZE..]
+  } else {
+    image
+  };
+
+  if !data.starts_with(DISK_VERIFICATION) {
+    return Err(CartridgeError::BadMagic);
+  }
+
+  let sides: Vec<Vec<u8>> = data
+    .chunks(DISK_SIDE_SIZE)
+    .filter(|side| side.starts_with(DISK_VERIFICATION))
+    .map(|side| {
+      let mut side = side.to_vec();
+      side.resize(DISK_SIDE_SIZE, 0);
+
+      side
+    })
+    .collect();
+
+  Ok(sides)
+}
+
+// strips the fwNES header, if any, so saves and diffs always refer to the bare disk data
+pub fn strip_header(image: &[u8]) -> &[u8] {
+  if image.starts_with(&FDS_ASCII) && image.len() >= FWNES_HEADER_SIZE {
+    &image[FWNES_HEADER_SIZE..]
+  } else {
+    image
+  }
+}
+
+fn block_length(side: &[u8], position: usize) -> Option<usize> {
+  match *side.get(position)? {
+    DISK_HEADER_BLOCK => Some(56),
+    FILE_COUNT_BLOCK => Some(2),
+    FILE_HEADER_BLOCK => Some(16),
+    // the file size lives in bytes 13 and 14 of the preceding file header block
+    FILE_DATA_BLOCK if position >= 3 => Some(1 + (side[position - 3] as usize | (side[position - 2] as usize) << 8)),
+    _ => None
+  }
+}
+
+// rebuilds the gaps, start marks and CRCs the drive actually streams past the head
+pub fn add_gaps(side: &[u8]) -> Vec<u8> {
+  let mut raw: Vec<u8> = vec![0; LEADING_GAP_SIZE];
+  let mut position = 0;
+
+  while let Some(length) = block_length(side, position) {
+    if position + length > side.len() {
+      break;
+    }
+
+    raw.push(BLOCK_START_MARK);
+    raw.extend_from_slice(&side[position..position + length]);
+    raw.extend_from_slice(&FAKE_CRC);
+    raw.resize(raw.len() + BLOCK_GAP_SIZE, 0);
+
+    position += length;
+  }
+
+  let raw_size = raw.len().max(DISK_SIDE_SIZE + LEADING_GAP_SIZE);
+  raw.resize(raw_size, 0);
+
+  raw
+}
+
+// the reverse of add_gaps, used when writing modified disks back out
+pub fn strip_gaps(raw: &[u8]) -> Vec<u8> {
+  let mut side: Vec<u8> = Vec::with_capacity(DISK_SIDE_SIZE);
+  let mut position = 0;
+
+  loop {
+    // skip the gap up to the next start mark
+    while position < raw.len() && raw[position] != BLOCK_START_MARK {
+      position += 1;
+    }
+    position += 1;
+
+    let block_start = side.len();
+
+    if position >= raw.len() {
+      break;
+    }
+
+    side.push(raw[position]);
+
+    let length = match block_length(&side, block_start) {
+      Some(length) => length,
+      None => {
+        side.pop();
+        break;
+      }
+    };
+
+    if position + length > raw.len() {
+      side.pop();
+      break;
+    }
+
+    side.extend_from_slice(&raw[position + 1..position + length]);
+
+    position += length + FAKE_CRC.len();
+  }
+
+  side.resize(DISK_SIDE_SIZE, 0);
+
+  side
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cartridge::Cartridge;
+  use crate::cpu::CPU;
+
+  // a side with the disk header, a file count of one and a single 4 byte file
+  fn side(disk_number: u8) -> Vec<u8> {
+    let mut side = DISK_VERIFICATION.to_vec();
+    side.resize(56, 0);
+    side[22] = disk_number;
+
+    side.extend_from_slice(&[FILE_COUNT_BLOCK, 1]);
+
+    let mut file_header = vec![0; 16];
+    file_header[0] = FILE_HEADER_BLOCK;
+    file_header[13] = 4;
+    side.extend(file_header);
+
+    side.extend_from_slice(&[FILE_DATA_BLOCK, 0xde, 0xad, 0xbe, 0xef]);
+    side
+  }
+
+  fn image(sides: &[Vec<u8>]) -> Vec<u8> {
+    let mut image = FDS_ASCII.to_vec();
+    image.push(sides.len() as u8);
+    image.resize(FWNES_HEADER_SIZE, 0);
+
+    for side in sides {
+      let start = image.len();
+
+      image.extend_from_slice(side);
+      image.resize(start + DISK_SIDE_SIZE, 0);
+    }
+
+    image
+  }
+
+  #[test]
+  fn parses_sides() {
+    let image = image(&[side(0), side(1)]);
+    let sides = parse(&image).unwrap();
+
+    assert_eq!(sides.len(), 2);
+    assert_eq!(sides[1][..side(1).len()], side(1)[..]);
+    assert_eq!(sides[1].len(), DISK_SIDE_SIZE);
+
+    // headerless images are the same data without the fwNES header
+    assert_eq!(parse(strip_header(&image)).unwrap(), sides);
+  }
+
+  #[test]
+  fn rejects_bad_images() {
+    assert!(matches!(parse(&FDS_ASCII), Err(CartridgeError::TruncatedHeader)));
+    assert!(matches!(parse(&[0; DISK_SIDE_SIZE]), Err(CartridgeError::BadMagic)));
+  }
+
+  #[test]
+  fn gaps_round_trip() {
+    let side = parse(&image(&[side(0)])).unwrap().remove(0);
+    let raw = add_gaps(&side);
+
+    assert_eq!(raw[LEADING_GAP_SIZE], BLOCK_START_MARK);
+    assert_eq!(raw[LEADING_GAP_SIZE + 1..LEADING_GAP_SIZE + 1 + DISK_VERIFICATION.len()], DISK_VERIFICATION[..]);
+    assert_eq!(strip_gaps(&raw), side);
+  }
+
+  #[test]
+  fn saves_disk_writes_as_a_patch_without_the_adapter_ram() {
+    let cartridge = Cartridge::new_fds(image(&[side(0)]), vec![0; BIOS_SIZE], Some("game.fds".to_string())).unwrap();
+
+    assert_eq!(cartridge.save_path.as_deref(), Some("game.ips"));
+
+    let mut cpu = CPU::new();
+    cpu.load_game(cartridge);
+    cpu.mem_write(0x6000, 0x42);
+
+    assert_eq!(cpu.save_data(), b"PATCHEOF");
+  }
+}
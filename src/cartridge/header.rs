@@ -0,0 +1,292 @@
+use super::{Mirroring, NES_ASCII, CartridgeError};
+
+pub const HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+
+const PRG_ROM_MULTIPLIER: usize = 16384;
+const CHR_ROM_MULTIPLIER: usize = 8192;
+
+// iNES headers that predate NES 2.0 assume 8KB of PRG-RAM when byte 8 is zero
+const DEFAULT_PRG_RAM_SIZE: usize = 8192;
+
+// extended console types up to 4 are a stock CPU and PPU, give or take a decimal mode or an
+// EPSM we don't emulate. past that are the VT0x/VT3xx and UM6578 famiclones and the Network System
+const MAX_EXTENDED_CONSOLE_TYPE: u8 = 4;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum HeaderFormat {
+  #[default]
+  INes,
+  Nes2
+}
+
+// see https://www.nesdev.org/wiki/NES_2.0#Byte_12_(CPU/PPU_Timing)
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum TimingRegion {
+  #[default]
+  Ntsc,
+  Pal,
+  MultiRegion,
+  Dendy
+}
+
+// see https://www.nesdev.org/wiki/NES_2.0#Byte_7_(Console_type)
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum ConsoleType {
+  #[default]
+  Nes,
+  VsSystem { ppu_type: u8, hardware_type: u8 },
+  Playchoice10,
+  Extended(u8)
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Header {
+  pub format: HeaderFormat,
+  pub mapper: u16,
+  pub submapper: u8,
+  pub prg_rom_size: usize,
+  pub chr_rom_size: usize,
+  pub prg_ram_size: usize,
+  pub prg_nvram_size: usize,
+  pub chr_ram_size: usize,
+  pub chr_nvram_size: usize,
+  pub mirroring: Mirroring,
+  pub has_battery: bool,
+  pub has_trainer: bool,
+  pub timing: TimingRegion,
+  pub console_type: ConsoleType,
+  pub misc_roms: u8,
+  pub default_expansion_device: u8
+}
+
+impl Header {
+  pub fn parse(rom: &[u8]) -> Result<Self, CartridgeError> {
+    if rom.len() < HEADER_SIZE {
+      return Err(CartridgeError::TruncatedHeader);
+    }
+
+    if rom[0..4] != NES_ASCII {
+      return Err(CartridgeError::BadMagic);
+    }
+
+    let four_screen = rom[6] & 0b1000 != 0;
+    let vertical_mirroring = rom[6] & 0b1 != 0;
+
+    let mirroring = if four_screen {
+      Mirroring::FourScreen
+    } else if vertical_mirroring {
+      Mirroring::Vertical
+    } else {
+      Mirroring::Horizontal
+    };
+
+    let mut header = Header {
+      mirroring,
+      has_battery: (rom[6] >> 1) & 0b1 == 1,
+      has_trainer: (rom[6] >> 2) & 0b1 == 1,
+      ..Default::default()
+    };
+
+    // per https://www.nesdev.org/wiki/NES_2.0#Identification
+    match (rom[7] >> 2) & 0b11 {
+      2 => header.parse_nes2(rom),
+      ines_ver => header.parse_ines(rom, ines_ver == 0 && rom[12..16].iter().all(|byte| *byte == 0))
+    }
+
+    if let ConsoleType::Extended(console_type) = header.console_type {
+      if console_type > MAX_EXTENDED_CONSOLE_TYPE {
+        return Err(CartridgeError::UnsupportedFormat(format!("extended console type {console_type}")));
+      }
+    }
+
+    Ok(header)
+  }
+
+  fn parse_ines(&mut self, rom: &[u8], upper_bytes_valid: bool) {
+    self.format = HeaderFormat::INes;
+
+    // old dumps tend to have garbage like "DiskDude!" written over bytes 7-15,
+    // in which case only the lower nibble of the mapper number can be trusted
+    let mapper_high = if upper_bytes_valid { rom[7] & 0b11110000 } else { 0 };
+
+    self.mapper = (mapper_high | (rom[6] >> 4)) as u16;
+    self.prg_rom_size = rom[4] as usize * PRG_ROM_MULTIPLIER;
+    self.chr_rom_size = rom[5] as usize * CHR_ROM_MULTIPLIER;
+
+    if upper_bytes_valid {
+      self.console_type = match rom[7] & 0b11 {
+        1 => ConsoleType::VsSystem { ppu_type: 0, hardware_type: 0 },
+        2 => ConsoleType::Playchoice10,
+        _ => ConsoleType::Nes
+      };
+
+      if rom[9] & 0b1 == 1 {
+        self.timing = TimingRegion::Pal;
+      }
+    }
+
+    let prg_ram_size = if upper_bytes_valid { rom[8] as usize * 8192 } else { 0 };
+    let prg_ram_size = if prg_ram_size == 0 { DEFAULT_PRG_RAM_SIZE } else { prg_ram_size };
+
+    if self.has_battery {
+      self.prg_nvram_size = prg_ram_size;
+    } else {
+      self.prg_ram_size = prg_ram_size;
+    }
+
+    if self.chr_rom_size == 0 {
+      self.chr_ram_size = 8192;
+    }
+  }
+
+  fn parse_nes2(&mut self, rom: &[u8]) {
+    self.format = HeaderFormat::Nes2;
+
+    self.mapper = ((rom[8] as u16 & 0b1111) << 8) | (rom[7] & 0b11110000) as u16 | (rom[6] >> 4) as u16;
+    self.submapper = rom[8] >> 4;
+
+    self.prg_rom_size = Self::rom_size(rom[4], rom[9] & 0b1111, PRG_ROM_MULTIPLIER);
+    self.chr_rom_size = Self::rom_size(rom[5], rom[9] >> 4, CHR_ROM_MULTIPLIER);
+
+    self.prg_ram_size = Self::ram_size(rom[10] & 0b1111);
+    self.prg_nvram_size = Self::ram_size(rom[10] >> 4);
+    self.chr_ram_size = Self::ram_size(rom[11] & 0b1111);
+    self.chr_nvram_size = Self::ram_size(rom[11] >> 4);
+
+    self.timing = match rom[12] & 0b11 {
+      0 => TimingRegion::Ntsc,
+      1 => TimingRegion::Pal,
+      2 => TimingRegion::MultiRegion,
+      3 => TimingRegion::Dendy,
+      _ => panic!("impossible")
+    };
+
+    self.console_type = match rom[7] & 0b11 {
+      0 => ConsoleType::Nes,
+      1 => ConsoleType::VsSystem { ppu_type: rom[13] & 0b1111, hardware_type: rom[13] >> 4 },
+      2 => ConsoleType::Playchoice10,
+      3 => ConsoleType::Extended(rom[13] & 0b1111),
+      _ => panic!("impossible")
+    };
+
+    self.misc_roms = rom[14] & 0b11;
+    self.default_expansion_device = rom[15] & 0b111111;
+  }
+
+  // per https://www.nesdev.org/wiki/NES_2.0#PRG-ROM_Area, if the MSB nibble is $F
+  // the size is given as 2^E * (MM*2+1) bytes instead of a count of banks
+  fn rom_size(lsb: u8, msb: u8, multiplier: usize) -> usize {
+    if msb == 0b1111 {
+      let exponent = (lsb >> 2) as u32;
+      let multiplier = (lsb & 0b11) as usize * 2 + 1;
+
+      2usize.saturating_pow(exponent).saturating_mul(multiplier)
+    } else {
+      ((msb as usize) << 8 | lsb as usize) * multiplier
+    }
+  }
+
+  // a shift count of 0 means no RAM, otherwise the size is 64 << shift
+  fn ram_size(shift: u8) -> usize {
+    if shift == 0 {
+      0
+    } else {
+      64 << shift
+    }
+  }
+
+  pub fn prg_rom_start(&self) -> usize {
+    HEADER_SIZE + if self.has_trainer { TRAINER_SIZE } else { 0 }
+  }
+
+  pub fn chr_rom_start(&self) -> usize {
+    self.prg_rom_start() + self.prg_rom_size
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Header, HeaderFormat, TimingRegion, ConsoleType};
+  use crate::cartridge::{Mirroring, CartridgeError};
+
+  fn header(bytes: [u8; 12]) -> [u8; 16] {
+    let mut rom = [0x4e, 0x45, 0x53, 0x1a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    rom[4..].copy_from_slice(&bytes);
+    rom
+  }
+
+  #[test]
+  fn parses_ines() {
+    let header = Header::parse(&header([2, 0, 0x43, 0x10, 0, 1, 0, 0, 0, 0, 0, 0])).unwrap();
+
+    assert_eq!(header.format, HeaderFormat::INes);
+    assert_eq!(header.mapper, 0x14);
+    assert_eq!(header.prg_rom_size, 32_768);
+    assert_eq!(header.chr_rom_size, 0);
+    assert_eq!(header.chr_ram_size, 8192);
+    assert_eq!(header.mirroring, Mirroring::Vertical);
+    assert!(header.has_battery);
+    assert!(!header.has_trainer);
+    assert_eq!((header.prg_ram_size, header.prg_nvram_size), (0, 8192));
+    assert_eq!(header.timing, TimingRegion::Pal);
+  }
+
+  #[test]
+  fn ignores_garbage_in_old_ines_headers() {
+    let mut rom = header([1, 1, 0x18, 0x44, 0, 0, 0, 0, 0, 0, 0, 0]);
+    rom[8..16].copy_from_slice(b"iskDude!");
+
+    let header = Header::parse(&rom).unwrap();
+
+    assert_eq!(header.mapper, 1);
+    assert_eq!(header.mirroring, Mirroring::FourScreen);
+    assert_eq!(header.prg_ram_size, 8192);
+    assert_eq!(header.console_type, ConsoleType::Nes);
+  }
+
+  #[test]
+  fn parses_nes2() {
+    let header = Header::parse(&header([0x40, 0x20, 0x52, 0x18, 0x31, 0x10, 0x70, 0x07, 0x03, 0, 0, 0x01])).unwrap();
+
+    assert_eq!(header.format, HeaderFormat::Nes2);
+    assert_eq!(header.mapper, 0x115);
+    assert_eq!(header.submapper, 3);
+    assert_eq!(header.prg_rom_size, 64 * 16_384);
+    assert_eq!(header.chr_rom_size, (0x100 + 0x20) * 8192);
+    assert_eq!(header.prg_ram_size, 0);
+    assert_eq!(header.prg_nvram_size, 64 << 7);
+    assert_eq!(header.chr_ram_size, 64 << 7);
+    assert_eq!(header.chr_nvram_size, 0);
+    assert_eq!(header.timing, TimingRegion::Dendy);
+    assert_eq!(header.default_expansion_device, 1);
+  }
+
+  #[test]
+  fn parses_nes2_exponent_sizes() {
+    // 2^10 * (1*2+1) bytes of PRG-ROM and 2^13 * 1 of CHR-ROM
+    let header = Header::parse(&header([0b101001, 0b110100, 0, 0x08, 0, 0xff, 0, 0, 0, 0, 0, 0])).unwrap();
+
+    assert_eq!(header.prg_rom_size, 3 * 1024);
+    assert_eq!(header.chr_rom_size, 8192);
+  }
+
+  #[test]
+  fn rejects_bad_headers() {
+    assert_eq!(Header::parse(&[0x4e, 0x45, 0x53]).unwrap_err(), CartridgeError::TruncatedHeader);
+    assert_eq!(Header::parse(&[0; 16]).unwrap_err(), CartridgeError::BadMagic);
+    assert!(matches!(
+      Header::parse(&header([1, 1, 0, 0x0b, 0, 0, 0, 0, 0, 0x05, 0, 0])),
+      Err(CartridgeError::UnsupportedFormat(_))
+    ));
+  }
+
+  #[test]
+  fn keeps_runnable_extended_console_types() {
+    // a famiclone with decimal mode
+    let header = Header::parse(&header([1, 1, 0, 0x0b, 0, 0, 0, 0, 0, 0x03, 0, 0])).unwrap();
+
+    assert_eq!(header.console_type, ConsoleType::Extended(3));
+  }
+}
@@ -0,0 +1,389 @@
+use super::CartridgeError;
+
+// see https://www.nesdev.org/wiki/NSF and https://www.nesdev.org/wiki/NSFe
+pub const NSF_ASCII: [u8; 5] = [0x4e, 0x45, 0x53, 0x4d, 0x1a];
+pub const NSFE_ASCII: [u8; 4] = [0x4e, 0x53, 0x46, 0x45];
+
+const NSF_HEADER_SIZE: usize = 0x80;
+const CHUNK_HEADER_SIZE: usize = 8;
+const TEXT_FIELD_SIZE: usize = 32;
+
+// NSF2 files may carry NSFe style metadata chunks after the program data
+const NSF2_METADATA_FLAG: u8 = 0b1000_0000;
+
+// 60.1 Hz, which is what nearly every NTSC rip asks for anyway
+pub const DEFAULT_NTSC_SPEED: u16 = 16639;
+pub const DEFAULT_PAL_SPEED: u16 = 19997;
+
+bitflags! {
+  #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+  pub struct ExpansionChips: u8 {
+    const VRC6 = 0b1;
+    const VRC7 = 0b10;
+    const FDS = 0b100;
+    const MMC5 = 0b1000;
+    const NAMCO_163 = 0b10000;
+    const SUNSOFT_5B = 0b100000;
+    const VT02 = 0b1000000;
+  }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TrackInfo {
+  pub name: Option<String>,
+  pub duration_ms: Option<u32>,
+  pub fade_ms: Option<u32>
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NsfFile {
+  pub version: u8,
+  pub total_songs: u8,
+  // zero based, unlike the header
+  pub starting_song: u8,
+  pub load_address: u16,
+  pub init_address: u16,
+  pub play_address: u16,
+  pub title: String,
+  pub artist: String,
+  pub copyright: String,
+  pub ripper: Option<String>,
+  pub ntsc_speed: u16,
+  pub pal_speed: u16,
+  pub bankswitch: [u8; 8],
+  pub is_pal: bool,
+  pub expansion_chips: ExpansionChips,
+  pub tracks: Vec<TrackInfo>,
+  // track order to play in, from the NSFe plst chunk
+  pub playlist: Option<Vec<u8>>,
+  pub data: Vec<u8>
+}
+
+pub fn is_nsf(file: &[u8]) -> bool {
+  file.starts_with(&NSF_ASCII) || file.starts_with(&NSFE_ASCII)
+}
+
+pub fn parse(file: &[u8]) -> Result<NsfFile, CartridgeError> {
+  let mut nsf = if file.starts_with(&NSFE_ASCII) {
+    parse_nsfe(file)?
+  } else {
+    parse_nsf(file)?
+  };
+
+  if nsf.ntsc_speed == 0 {
+    nsf.ntsc_speed = DEFAULT_NTSC_SPEED;
+  }
+  if nsf.pal_speed == 0 {
+    nsf.pal_speed = DEFAULT_PAL_SPEED;
+  }
+
+  nsf.tracks.resize(nsf.total_songs as usize, TrackInfo::default());
+
+  if nsf.starting_song >= nsf.total_songs {
+    nsf.starting_song = 0;
+  }
+
+  Ok(nsf)
+}
+
+fn parse_nsf(file: &[u8]) -> Result<NsfFile, CartridgeError> {
+  if file.len() < NSF_HEADER_SIZE {
+    return Err(CartridgeError::TruncatedHeader);
+  }
+
+  if !file.starts_with(&NSF_ASCII) {
+    return Err(CartridgeError::BadMagic);
+  }
+
+  let mut bankswitch = [0; 8];
+  bankswitch.copy_from_slice(&file[0x70..0x78]);
+
+  let mut nsf = NsfFile {
+    version: file[5],
+    total_songs: file[6],
+    starting_song: file[7].saturating_sub(1),
+    load_address: read_u16(file, 0x08),
+    init_address: read_u16(file, 0x0a),
+    play_address: read_u16(file, 0x0c),
+    title: read_text(&file[0x0e..0x0e + TEXT_FIELD_SIZE]),
+    artist: read_text(&file[0x2e..0x2e + TEXT_FIELD_SIZE]),
+    copyright: read_text(&file[0x4e..0x4e + TEXT_FIELD_SIZE]),
+    ntsc_speed: read_u16(file, 0x6e),
+    pal_speed: read_u16(file, 0x78),
+    bankswitch,
+    // bit 0 is PAL, bit 1 means the tune plays on both
+    is_pal: file[0x7a] & 0b11 == 0b01,
+    expansion_chips: ExpansionChips::from_bits_truncate(file[0x7b]),
+    ..Default::default()
+  };
+
+  let data = &file[NSF_HEADER_SIZE..];
+
+  // NSF2 stores the program length so metadata chunks can follow it, 0 means the rest of the file
+  let data_length = (file[0x7d] as usize) | (file[0x7e] as usize) << 8 | (file[0x7f] as usize) << 16;
+
+  if nsf.version >= 2 && data_length != 0 && data_length <= data.len() {
+    nsf.data = data[..data_length].to_vec();
+
+    if file[0x7c] & NSF2_METADATA_FLAG != 0 {
+      parse_chunks(&data[data_length..], &mut nsf)?;
+    }
+  } else {
+    nsf.data = data.to_vec();
+  }
+
+  Ok(nsf)
+}
+
+fn parse_nsfe(file: &[u8]) -> Result<NsfFile, CartridgeError> {
+  let mut nsf = NsfFile {
+    total_songs: 1,
+    ..Default::default()
+  };
+
+  let found = parse_chunks(&file[NSFE_ASCII.len()..], &mut nsf)?;
+
+  if !found.info {
+    return Err(CartridgeError::UnsupportedFormat("NSFe file has no INFO chunk".to_string()));
+  }
+  if !found.data {
+    return Err(CartridgeError::UnsupportedFormat("NSFe file has no DATA chunk".to_string()));
+  }
+
+  Ok(nsf)
+}
+
+#[derive(Default)]
+struct FoundChunks {
+  info: bool,
+  data: bool
+}
+
+fn parse_chunks(mut chunks: &[u8], nsf: &mut NsfFile) -> Result<FoundChunks, CartridgeError> {
+  let mut found = FoundChunks::default();
+
+  while chunks.len() >= CHUNK_HEADER_SIZE {
+    let length = u32::from_le_bytes([chunks[0], chunks[1], chunks[2], chunks[3]]) as usize;
+    let id = &chunks[4..8];
+
+    let data_end = CHUNK_HEADER_SIZE.saturating_add(length);
+
+    if data_end > chunks.len() {
+      return Err(CartridgeError::TruncatedChunk(String::from_utf8_lossy(id).into_owned()));
+    }
+
+    let data = &chunks[CHUNK_HEADER_SIZE..data_end];
+
+    match id {
+      b"INFO" => {
+        if data.len() < 8 {
+          return Err(CartridgeError::TruncatedChunk("INFO".to_string()));
+        }
+
+        nsf.load_address = read_u16(data, 0);
+        nsf.init_address = read_u16(data, 2);
+        nsf.play_address = read_u16(data, 4);
+        nsf.is_pal = data[6] & 0b11 == 0b01;
+        nsf.expansion_chips = ExpansionChips::from_bits_truncate(data[7]);
+        nsf.total_songs = data.get(8).copied().unwrap_or(1);
+        nsf.starting_song = data.get(9).copied().unwrap_or(0);
+
+        found.info = true;
+      }
+      b"DATA" => {
+        nsf.data = data.to_vec();
+
+        found.data = true;
+      }
+      b"BANK" => {
+        for (bank, byte) in nsf.bankswitch.iter_mut().zip(data) {
+          *bank = *byte;
+        }
+      }
+      b"RATE" => {
+        if data.len() >= 2 {
+          nsf.ntsc_speed = read_u16(data, 0);
+        }
+        if data.len() >= 4 {
+          nsf.pal_speed = read_u16(data, 2);
+        }
+      }
+      b"auth" => {
+        let mut fields = data.split(|byte| *byte == 0).map(read_text);
+
+        nsf.title = fields.next().unwrap_or_default();
+        nsf.artist = fields.next().unwrap_or_default();
+        nsf.copyright = fields.next().unwrap_or_default();
+        nsf.ripper = fields.next().filter(|ripper| !ripper.is_empty());
+      }
+      b"tlbl" => {
+        let names = data.split(|byte| *byte == 0).map(read_text);
+
+        for (index, name) in names.enumerate().take(nsf.total_songs as usize) {
+          track(nsf, index).name = Some(name);
+        }
+      }
+      b"time" | b"fade" => {
+        for (index, time) in data.chunks_exact(4).enumerate().take(nsf.total_songs as usize) {
+          // negative means the default should be used
+          let time = i32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+          let time = u32::try_from(time).ok();
+
+          if id == b"time" {
+            track(nsf, index).duration_ms = time;
+          } else {
+            track(nsf, index).fade_ms = time;
+          }
+        }
+      }
+      b"plst" => nsf.playlist = Some(data.to_vec()),
+      b"NEND" => break,
+      // chunks starting with an uppercase letter have to be understood to play the file
+      [first, ..] if first.is_ascii_uppercase() => {
+        return Err(CartridgeError::UnsupportedFormat(format!("unknown NSFe chunk {}", String::from_utf8_lossy(id))));
+      }
+      _ => ()
+    }
+
+    chunks = &chunks[data_end..];
+  }
+
+  Ok(found)
+}
+
+fn track(nsf: &mut NsfFile, index: usize) -> &mut TrackInfo {
+  if nsf.tracks.len() <= index {
+    nsf.tracks.resize(index + 1, TrackInfo::default());
+  }
+
+  &mut nsf.tracks[index]
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+  u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_text(data: &[u8]) -> String {
+  let end = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+
+  String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+impl NsfFile {
+  pub fn is_bankswitched(&self) -> bool {
+    self.bankswitch.iter().any(|bank| *bank != 0)
+  }
+
+  // time between PLAY calls in microseconds
+  pub fn play_speed(&self) -> u16 {
+    if self.is_pal {
+      self.pal_speed
+    } else {
+      self.ntsc_speed
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn nsf_header() -> Vec<u8> {
+    let mut file = NSF_ASCII.to_vec();
+    file.resize(NSF_HEADER_SIZE, 0);
+
+    file[5] = 1;
+    file[6] = 3;
+    file[7] = 2;
+    file[0x08..0x0e].copy_from_slice(&[0x00, 0x80, 0x03, 0x80, 0x06, 0x80]);
+    file[0x0e..0x0e + 4].copy_from_slice(b"Song");
+    file[0x2e..0x2e + 6].copy_from_slice(b"Artist");
+    file[0x4e..0x4e + 4].copy_from_slice(b"2024");
+    file[0x72] = 1;
+    file[0x7b] = ExpansionChips::VRC6.bits();
+    file
+  }
+
+  fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_le_bytes().to_vec();
+
+    chunk.extend_from_slice(id);
+    chunk.extend_from_slice(data);
+    chunk
+  }
+
+  #[test]
+  fn parses_nsf() {
+    let mut file = nsf_header();
+    file.extend_from_slice(&[0xea; 16]);
+
+    let nsf = parse(&file).unwrap();
+
+    assert_eq!((nsf.total_songs, nsf.starting_song), (3, 1));
+    assert_eq!((nsf.load_address, nsf.init_address, nsf.play_address), (0x8000, 0x8003, 0x8006));
+    assert_eq!((nsf.title.as_str(), nsf.artist.as_str(), nsf.copyright.as_str()), ("Song", "Artist", "2024"));
+    assert_eq!(nsf.bankswitch, [0, 0, 1, 0, 0, 0, 0, 0]);
+    assert!(nsf.is_bankswitched());
+    assert_eq!(nsf.expansion_chips, ExpansionChips::VRC6);
+    // a speed of 0 falls back to the usual 60.1 Hz
+    assert_eq!(nsf.play_speed(), DEFAULT_NTSC_SPEED);
+    assert_eq!(nsf.tracks.len(), 3);
+    assert_eq!(nsf.data, vec![0xea; 16]);
+  }
+
+  #[test]
+  fn parses_nsf2_metadata() {
+    let mut file = nsf_header();
+    file[5] = 2;
+    file[0x7c] = NSF2_METADATA_FLAG;
+    file[0x7d] = 4;
+    file.extend_from_slice(&[0xea; 4]);
+    file.extend(chunk(b"tlbl", b"One\0Two\0Three\0"));
+
+    let nsf = parse(&file).unwrap();
+
+    assert_eq!(nsf.data, vec![0xea; 4]);
+    assert_eq!(nsf.tracks[1].name.as_deref(), Some("Two"));
+  }
+
+  #[test]
+  fn parses_nsfe() {
+    let mut file = NSFE_ASCII.to_vec();
+    file.extend(chunk(b"INFO", &[0x00, 0x80, 0x03, 0x80, 0x06, 0x80, 0x01, 0x00, 2, 1]));
+    file.extend(chunk(b"DATA", &[0xea; 8]));
+    file.extend(chunk(b"auth", b"Song\0Artist\0\0Ripper\0"));
+    file.extend(chunk(b"time", &[0x10, 0x27, 0, 0, 0xff, 0xff, 0xff, 0xff]));
+    file.extend(chunk(b"plst", &[1, 0]));
+    file.extend(chunk(b"NEND", &[]));
+
+    let nsf = parse(&file).unwrap();
+
+    assert_eq!((nsf.total_songs, nsf.starting_song), (2, 1));
+    assert!(nsf.is_pal);
+    assert_eq!(nsf.play_speed(), DEFAULT_PAL_SPEED);
+    assert_eq!(nsf.ripper.as_deref(), Some("Ripper"));
+    assert_eq!(nsf.tracks[0].duration_ms, Some(10_000));
+    // negative times mean the player's default
+    assert_eq!(nsf.tracks[1].duration_ms, None);
+    assert_eq!(nsf.playlist, Some(vec![1, 0]));
+    assert_eq!(nsf.data, vec![0xea; 8]);
+  }
+
+  #[test]
+  fn rejects_bad_files() {
+    assert!(matches!(parse(&NSF_ASCII), Err(CartridgeError::TruncatedHeader)));
+
+    let mut missing_data = NSFE_ASCII.to_vec();
+    missing_data.extend(chunk(b"INFO", &[0; 10]));
+    assert!(matches!(parse(&missing_data), Err(CartridgeError::UnsupportedFormat(_))));
+
+    let mut unknown_chunk = missing_data.clone();
+    unknown_chunk.extend(chunk(b"ZZZZ", &[]));
+    assert!(matches!(parse(&unknown_chunk), Err(CartridgeError::UnsupportedFormat(_))));
+
+    let mut truncated = NSFE_ASCII.to_vec();
+    truncated.extend_from_slice(&[0xff, 0, 0, 0]);
+    truncated.extend_from_slice(b"DATA");
+    assert!(matches!(parse(&truncated), Err(CartridgeError::TruncatedChunk(_))));
+  }
+}
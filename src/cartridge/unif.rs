@@ -0,0 +1,206 @@
+use super::{Mirroring, CartridgeError};
+use super::header::{Header, TimingRegion};
+
+// see https://www.nesdev.org/wiki/UNIF
+pub const UNIF_ASCII: [u8; 4] = [0x55, 0x4e, 0x49, 0x46];
+
+const UNIF_HEADER_SIZE: usize = 32;
+const CHUNK_HEADER_SIZE: usize = 8;
+
+const DEFAULT_PRG_RAM_SIZE: usize = 8192;
+const DEFAULT_CHR_RAM_SIZE: usize = 8192;
+
+pub struct UnifImage {
+  pub board: String,
+  pub header: Header,
+  pub prg_rom: Vec<u8>,
+  pub chr_rom: Vec<u8>
+}
+
+pub fn parse(rom: &[u8]) -> Result<UnifImage, CartridgeError> {
+  if rom.len() < UNIF_HEADER_SIZE {
+    return Err(CartridgeError::TruncatedHeader);
+  }
+
+  if rom[0..4] != UNIF_ASCII {
+    return Err(CartridgeError::BadMagic);
+  }
+
+  let mut board: Option<String> = None;
+  let mut prg_chunks: [Option<&[u8]>; 16] = [None; 16];
+  let mut chr_chunks: [Option<&[u8]>; 16] = [None; 16];
+  let mut mirroring: Option<Mirroring> = None;
+  let mut has_battery = false;
+  let mut timing = TimingRegion::Ntsc;
+
+  let mut offset = UNIF_HEADER_SIZE;
+
+  while offset + CHUNK_HEADER_SIZE <= rom.len() {
+    let id = &rom[offset..offset + 4];
+    let length = u32::from_le_bytes([rom[offset + 4], rom[offset + 5], rom[offset + 6], rom[offset + 7]]) as usize;
+
+    let data_start = offset + CHUNK_HEADER_SIZE;
+    let data_end = data_start.saturating_add(length);
+
+    if data_end > rom.len() {
+      return Err(CartridgeError::TruncatedChunk(String::from_utf8_lossy(id).into_owned()));
+    }
+
+    let data = &rom[data_start..data_end];
+
+    match id {
+      b"MAPR" => {
+        let name_end = data.iter().position(|byte| *byte == 0).unwrap_or(data.len());
+
+        board = Some(String::from_utf8_lossy(&data[..name_end]).trim().to_string());
+      }
+      [b'P', b'R', b'G', index] => {
+        if let Some(index) = chunk_index(*index) {
+          prg_chunks[index] = Some(data);
+        }
+      }
+      [b'C', b'H', b'R', index] => {
+        if let Some(index) = chunk_index(*index) {
+          chr_chunks[index] = Some(data);
+        }
+      }
+      b"MIRR" => {
+        mirroring = match data.first() {
+          Some(0) => Some(Mirroring::Horizontal),
+          Some(1) => Some(Mirroring::Vertical),
+          Some(2) => Some(Mirroring::SingleScreenA),
+          Some(3) => Some(Mirroring::SingleScreenB),
+          Some(4) => Some(Mirroring::FourScreen),
+          // 5 means the mapper controls mirroring, which it will do on its own
+          _ => None
+        };
+      }
+      b"BATR" => has_battery = true,
+      b"TVCI" => {
+        timing = match data.first() {
+          Some(1) => TimingRegion::Pal,
+          Some(2) => TimingRegion::MultiRegion,
+          _ => TimingRegion::Ntsc
+        };
+      }
+      _ => ()
+    }
+
+    offset = data_end;
+  }
+
+  let board = board.ok_or(CartridgeError::UnsupportedFormat("UNIF file has no MAPR chunk".to_string()))?;
+
+  let (mapper, submapper) = board_mapper(&board).ok_or(CartridgeError::UnsupportedBoard(board.clone()))?;
+
+  // PRG0-PRGF and CHR0-CHRF are concatenated in order, per the spec
+  let prg_rom: Vec<u8> = prg_chunks.iter().flatten().flat_map(|chunk| chunk.iter().copied()).collect();
+  let chr_rom: Vec<u8> = chr_chunks.iter().flatten().flat_map(|chunk| chunk.iter().copied()).collect();
+
+  let header = Header {
+    mapper,
+    submapper,
+    prg_rom_size: prg_rom.len(),
+    chr_rom_size: chr_rom.len(),
+    prg_ram_size: if has_battery { 0 } else { DEFAULT_PRG_RAM_SIZE },
+    prg_nvram_size: if has_battery { DEFAULT_PRG_RAM_SIZE } else { 0 },
+    chr_ram_size: if chr_rom.is_empty() { DEFAULT_CHR_RAM_SIZE } else { 0 },
+    mirroring: mirroring.unwrap_or_default(),
+    has_battery,
+    timing,
+    ..Default::default()
+  };
+
+  Ok(UnifImage {
+    board,
+    header,
+    prg_rom,
+    chr_rom
+  })
+}
+
+fn chunk_index(index: u8) -> Option<usize> {
+  (index as char).to_digit(16).map(|index| index as usize)
+}
+
+// maps UNIF board names onto the iNES mapper (and submapper) that emulates them.
+// see https://www.nesdev.org/wiki/UNIF#Board_names
+pub fn board_mapper(board: &str) -> Option<(u16, u8)> {
+  let name = ["NES-", "HVC-", "UNL-", "BTL-", "BMC-", "MLT-", "AVE-", "CAMERICA-", "IREM-", "KONAMI-"]
+    .iter()
+    .find_map(|prefix| board.strip_prefix(prefix))
+    .unwrap_or(board);
+
+  let mapper = match name {
+    "NROM" | "NROM-128" | "NROM-256" | "RROM" | "RROM-128" => (0, 0),
+    "SAROM" | "SBROM" | "SCROM" | "SC1ROM" | "SEROM" | "SFROM" | "SGROM" | "SHROM"
+      | "SJROM" | "SKROM" | "SLROM" | "SL1ROM" | "SL2ROM" | "SL3ROM" | "SLRROM"
+      | "SNROM" | "SOROM" | "SUROM" | "SXROM" => (1, 0),
+    "UNROM" | "UOROM" => (2, 0),
+    "CNROM" => (3, 0),
+    "TBROM" | "TEROM" | "TFROM" | "TGROM" | "TKROM" | "TLROM" | "TL1ROM" | "TL2ROM"
+      | "TR1ROM" | "TSROM" | "TVROM" | "B4" => (4, 0),
+    "HKROM" => (4, 1),
+    "EKROM" | "ELROM" | "ETROM" | "EWROM" => (5, 0),
+    "ANROM" | "AN1ROM" | "AMROM" | "AOROM" => (7, 0),
+    "PNROM" | "PEEOROM" => (9, 0),
+    "FJROM" | "FKROM" => (10, 0),
+    "GNROM" | "MHROM" => (66, 0),
+    "BNROM" => (34, 2),
+    // American Video Entertainment's boards, NINA-001/002 on mapper 34 and NINA-03/06 on 79
+    "NINA-01" | "NINA-02" => (34, 1),
+    "NINA-03" | "NINA-06" => (79, 0),
+    // the BF9097 is Fire Hawk's, with single screen mirroring
+    "BF9093" | "ALGN" => (71, 0),
+    "BF9097" => (71, 1),
+    "ACTION52" => (228, 0),
+    "DEROM" | "DE1ROM" | "DRROM" => (206, 0),
+    "TKSROM" | "TLSROM" => (118, 0),
+    "TQROM" => (119, 0),
+    _ => return None
+  };
+
+  Some(mapper)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::board_mapper;
+  use crate::cartridge::{Cartridge, Mirroring};
+
+  fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    chunk
+  }
+
+  #[test]
+  fn loads_a_minimal_image() {
+    let mut rom = b"UNIF".to_vec();
+    rom.extend_from_slice(&7u32.to_le_bytes());
+    rom.resize(32, 0);
+
+    rom.extend(chunk(b"MAPR", b"AVE-NINA-06\0"));
+    rom.extend(chunk(b"PRG0", &[0xaa; 32_768]));
+    rom.extend(chunk(b"CHR0", &[0x55; 16_384]));
+    rom.extend(chunk(b"MIRR", &[1]));
+
+    let cartridge = Cartridge::new(rom, None).unwrap();
+
+    assert_eq!(cartridge.header.mapper, 79);
+    assert_eq!(cartridge.header.mirroring, Mirroring::Vertical);
+    assert_eq!(cartridge.prg_rom, vec![0xaa; 32_768]);
+    assert_eq!(cartridge.chr_rom, vec![0x55; 16_384]);
+  }
+
+  #[test]
+  fn maps_board_names() {
+    assert_eq!(board_mapper("NES-TLROM"), Some((4, 0)));
+    assert_eq!(board_mapper("MLT-ACTION52"), Some((228, 0)));
+    assert_eq!(board_mapper("CAMERICA-BF9097"), Some((71, 1)));
+    assert_eq!(board_mapper("AVE-NINA-01"), Some((34, 1)));
+    assert_eq!(board_mapper("UNL-NOT-A-BOARD"), None);
+  }
+}
@@ -6,9 +6,7 @@ use std::io::Write;
 use std::fs;
 use std::path::Path;
 
-use crate::mapper::{MapperActions, Mapper};
-
-use super::cartridge::{Cartridge, Mirroring};
+use super::cartridge::{Cartridge, header::Header};
 use ppu::PPU;
 use apu::APU;
 
@@ -19,9 +17,13 @@ pub struct CPU {
   pub apu: APU,
   pub prg_length: usize,
   pub prg_save: bool,
+  pub header: Header,
+  pub has_battery: bool,
   cycles: u16,
   total_cycles: u64,
-  file_path: Option<String>,
+  // the last value on the data bus, which reads nothing answers get back
+  open_bus: u8,
+  save_path: Option<String>,
   memory: [u8; 0x800],
   prg_rom: Vec<u8>,
 }
@@ -29,6 +31,10 @@ pub struct CPU {
 const STACK_BASE_ADDR: u16 = 0x0100;
 const STACK_START: u8 = 0xfd;
 
+// trainers live at $7000-$71ff, i.e. $1000 bytes into PRG-RAM
+const TRAINER_ADDRESS: usize = 0x1000;
+const PRG_RAM_SIZE: usize = 0x2000;
+
 const NMI_INTERRUPT_VECTOR_ADDRESS: u16 = 0xfffa;
 const IRQ_INTERRUPT_VECTOR_ADDRESS: u16 = 0xfffe;
 
@@ -69,32 +75,95 @@ impl CPU {
       prg_rom: Vec::new(),
       prg_ram: Vec::new(),
       prg_length: 0,
-      ppu: PPU::new(Vec::new(), Vec::new(), Mirroring::Vertical),
+      ppu: PPU::new(Vec::new(), Vec::new()),
       apu: APU::new(),
       cycles: 0,
       total_cycles: 0,
-      file_path: None,
-      prg_save: false
+      open_bus: 0,
+      save_path: None,
+      prg_save: false,
+      header: Header::default(),
+      has_battery: false
     }
   }
 
+  pub fn set_save_path(&mut self, save_path: Option<String>) {
+    self.save_path = save_path;
+  }
+
+  pub fn save_path(&self) -> Option<&str> {
+    self.save_path.as_deref()
+  }
+
   pub fn save_game(&mut self) {
-    if let Some(file_path) = &self.file_path {
-      if self.prg_ram.len() > 0 && self.prg_save {
+    if !self.has_battery {
+      return;
+    }
+
+    if !self.prg_save && !self.ppu.mapper.save_pending() {
+      return;
+    }
+
+    let save = self.save_data();
+
+    if let Some(save_path) = &self.save_path {
+      if !save.is_empty() {
         let mut file = fs::OpenOptions::new()
           .create(true)
           .write(true)
-          .open(file_path)
+          .truncate(true)
+          .open(save_path)
           .unwrap();
 
-        let _ = file.write_all(&self.prg_ram);
+        let _ = file.write_all(&save);
 
         self.prg_save = false;
       }
     }
   }
 
+  // battery backed PRG-RAM followed by whatever the mapper keeps battery backed on its own
+  pub fn save_data(&mut self) -> Vec<u8> {
+    let mut save = self.prg_ram[..self.prg_nvram_length()].to_vec();
+
+    if let Some(mapper_save) = self.ppu.mapper.save_data() {
+      save.extend_from_slice(&mapper_save);
+    }
+
+    save
+  }
+
+  pub fn load_save_data(&mut self, save: &[u8]) {
+    let length = save.len().min(self.prg_nvram_length());
+
+    self.prg_ram[..length].copy_from_slice(&save[..length]);
+
+    if save.len() > length {
+      self.ppu.mapper.load_save_data(&save[length..]);
+    }
+  }
+
+  // only the part of PRG-RAM the header says is battery backed goes in a save
+  fn prg_nvram_length(&self) -> usize {
+    self.header.prg_nvram_size.min(self.prg_ram.len())
+  }
+
   pub fn mem_read(&mut self, address: u16) -> u8 {
+    let val = self.read_bus(address);
+
+    self.open_bus = val;
+
+    val
+  }
+
+  fn read_bus(&mut self, address: u16) -> u8 {
+    // the expansion area at $4020-$5fff and everything above it is the cartridge's
+    if address >= 0x4020 {
+      if let Some(val) = self.ppu.mapper.cpu_read(address) {
+        return val;
+      }
+    }
+
     match address {
       0x0000 ..= 0x1fff => self.memory[(address & 0b11111111111) as usize],
       0x2000 | 0x2001 | 0x2003 | 0x2005 | 0x2006 | 0x4014 => 0,
@@ -109,34 +178,20 @@ impl CPU {
       }
       0x4015 => self.apu.read_status(),
       0x4016 => self.ppu.joypad.read(),
+      // nothing on the board answered
+      0x4020 ..= 0x5fff => self.open_bus,
       0x6000 ..= 0x7fff => {
         if let Some(mapped_address) = self.ppu.mapper.mem_read(address) {
-          self.prg_ram[mapped_address]
+          self.prg_ram.get(mapped_address).copied().unwrap_or(0)
         } else {
           0
         }
       }
       0x8000 ..= 0xffff => {
-
-        match &mut self.ppu.mapper {
-          Mapper::Empty(_) | Mapper::Cnrom(_) => {
-            let prg_address = address - 0x8000;
-
-            if self.prg_length == 0x4000 && prg_address >= 0x4000 {
-              let actual_address = prg_address % 0x4000;
-
-              self.prg_rom[actual_address as usize]
-            } else {
-              self.prg_rom[prg_address as usize]
-            }
-          }
-          _ => {
-            if let Some(mapped_address) = self.ppu.mapper.mem_read(address) {
-              self.prg_rom[mapped_address]
-            } else {
-              0
-            }
-          }
+        if let Some(mapped_address) = self.ppu.mapper.mem_read(address) {
+          self.prg_rom[mapped_address]
+        } else {
+          0
         }
       }
       _ => 0
@@ -144,6 +199,13 @@ impl CPU {
   }
 
   pub fn mem_write(&mut self, address: u16, value: u8) {
+    self.open_bus = value;
+
+    // PPU register writes go past the mapper too, since some boards watch them
+    if address >= 0x2000 && self.ppu.mapper.cpu_write(address, value) {
+      return;
+    }
+
     match address {
       0x0000 ..= 0x1fff => self.memory[(address & 0b11111111111) as usize] = value,
       0x2000 => self.ppu.write_to_control(value),
@@ -184,13 +246,14 @@ impl CPU {
       0x4017 => self.apu.write_frame_counter(value),
       0x6000..=0x7fff => {
         if let Some(mapped_address) = self.ppu.mapper.mem_write(address, value) {
-          self.prg_ram[mapped_address as usize] = value;
-          self.prg_save = true;
+          if let Some(byte) = self.prg_ram.get_mut(mapped_address) {
+            *byte = value;
+            self.prg_save = true;
+          }
         }
       }
       0x8000..=0xffff => {
         self.ppu.mapper.mem_write(address, value);
-        self.ppu.update_mirroring();
       }
       _ => self.ignore_write()
     };
@@ -240,31 +303,61 @@ impl CPU {
 
   pub fn load_game(&mut self, cartridge: Cartridge) {
     self.prg_length = cartridge.prg_rom.len();
-    let prg_ram_length = cartridge.prg_ram.len();
+    self.has_battery = cartridge.has_battery();
 
     self.prg_rom = cartridge.prg_rom;
     self.prg_ram = cartridge.prg_ram;
     self.ppu.chr_rom = cartridge.chr_rom;
     self.ppu.chr_ram = cartridge.chr_ram;
-    self.ppu.mirroring = cartridge.mirroring;
+    self.ppu.cartridge_vram = cartridge.vram;
     self.ppu.mapper = cartridge.mapper;
-    self.ppu.update_mirroring();
+    self.header = cartridge.header;
 
-    if let Some(file_path) = cartridge.path {
-      self.file_path = Some(file_path.replace(".nes", ".sav"));
-    }
+    self.save_path = cartridge.save_path;
 
-    if prg_ram_length > 0 {
+    if self.has_battery {
       self.load_ram()
     }
 
+    if let Some(trainer) = cartridge.trainer {
+      self.load_trainer(&trainer);
+    }
+
+    self.registers.pc = self.mem_read_u16(0xfffc);
+  }
+
+  // the console's reset button: the CPU goes back through the reset vector with
+  // interrupts disabled, the APU and PPU are silenced and boards wired to the reset line
+  // get to react, e.g. multicarts returning to their menu
+  // see https://www.nesdev.org/wiki/CPU_power_up_state#After_reset
+  pub fn reset(&mut self) {
+    self.ppu.mapper.reset();
+    self.ppu.write_to_control(0);
+    self.ppu.write_to_mask(0);
+    self.apu.write_status(0);
+
+    self.registers.sp = self.registers.sp.wrapping_sub(3);
+    self.registers.p.insert(CpuFlags::INTERRUPT_DISABLE);
     self.registers.pc = self.mem_read_u16(0xfffc);
   }
 
+  // see https://www.nesdev.org/wiki/INES#Trainer
+  fn load_trainer(&mut self, trainer: &[u8]) {
+    let trainer_end = TRAINER_ADDRESS + trainer.len();
+
+    if self.prg_ram.len() < PRG_RAM_SIZE {
+      self.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    self.prg_ram[TRAINER_ADDRESS..trainer_end].copy_from_slice(trainer);
+  }
+
   pub fn load_ram(&mut self) {
-    if let Some(file_path) = &self.file_path {
-      if Path::new(file_path).exists() {
-        self.prg_ram = fs::read(file_path).unwrap();
+    if let Some(save_path) = &self.save_path {
+      if Path::new(save_path).exists() {
+        let save = fs::read(save_path).unwrap();
+
+        self.load_save_data(&save);
       }
     }
   }
@@ -346,6 +439,7 @@ impl CPU {
   pub fn cycle(&mut self, cycles: u16) {
     self.cycles += cycles;
     self.total_cycles = self.total_cycles.wrapping_add(cycles as u64);
+    self.apu.expansion_output = self.ppu.mapper.audio_output();
     self.apu.tick(cycles);
 
     let ppu_cycles = cycles * 3;
@@ -354,6 +448,31 @@ impl CPU {
       self.ppu.tick();
     }
 
-    self.ppu.mapper.tick(cycles as u8);
+    self.ppu.mapper.tick(cycles);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::CPU;
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn routes_the_expansion_area_to_the_mapper() {
+    let mut cpu = CPU::new();
+
+    // Action 52 keeps four bits of RAM at $4020-$5fff
+    cpu.load_game(test_cartridge(228, 0, 8, 8));
+
+    cpu.mem_write(0x5123, 0x0a);
+    cpu.mem_write(0x0000, 0x77);
+
+    assert_eq!(cpu.mem_read(0x5123), 0x0a);
+
+    // without a board answering, reads get whatever was last on the bus
+    cpu.load_game(test_cartridge(0, 0, 2, 1));
+    cpu.mem_write(0x0000, 0x77);
+
+    assert_eq!(cpu.mem_read(0x5123), 0x77);
   }
-}
\ No newline at end of file
+}
@@ -8,7 +8,13 @@ pub mod registers;
 pub mod frame_counter;
 pub mod envelope;
 
-const CYCLES_PER_SAMPLE: usize = 1790000 / 44100;
+pub const SAMPLE_RATE: usize = 44100;
+
+const CYCLES_PER_SAMPLE: usize = 1790000 / SAMPLE_RATE;
+
+// one pulse at full volume through the mixer below, which expansion audio is levelled against
+// see https://www.nesdev.org/wiki/APU_Mixer
+pub const FULL_VOLUME_PULSE: f32 = 95.52 / (8_128.0 / 15.0 + 100.0);
 
 pub struct APU {
   pub pulse1: Pulse,
@@ -22,6 +28,7 @@ pub struct APU {
   pub buffer_index: usize,
   pub previous_value: f32,
   pub irq_pending: bool,
+  pub expansion_output: f32,
   cycles: usize,
   half_cycle: u8,
   irq_inhibit: bool,
@@ -59,6 +66,7 @@ impl APU {
       frame_counter: FrameCounter::new(),
       irq_inhibit: false,
       irq_pending: false,
+      expansion_output: 0.0,
       status: Status::from_bits_truncate(0b0),
       pulse_table,
       tnd_table,
@@ -183,7 +191,7 @@ impl APU {
     // let tnd_index = (3.0 * triangle_out + ((2.0 * noise_out) + dmc_out)) as usize % self.tnd_table.len();
     let tnd_index = (3.0f32.mul_add(triangle_out, 2.0 * noise_out) + dmc_out) as usize % self.tnd_table.len();
 
-    self.pulse_table[pulse_index] + self.tnd_table[tnd_index]
+    self.pulse_table[pulse_index] + self.tnd_table[tnd_index] + self.expansion_output
   }
 
   pub fn write_frame_counter(&mut self, val: u8) {
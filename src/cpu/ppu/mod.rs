@@ -13,8 +13,7 @@ use self::joypad::Joypad;
 
 use picture::Picture;
 
-use crate::cartridge::Mirroring;
-use crate::mapper::{Mapper, Empty, MapperActions};
+use crate::mapper::{Mapper, Empty, Nametable, PpuFetch};
 
 pub const SCANLINES_PER_FRAME: u16 = 262;
 const CYCLES_PER_SCANLINE: u16 = 341;
@@ -190,6 +189,17 @@ const PALETTE_TABLE: [(u8, u8, u8); 64] = [
 //   (0,0,0)
 // ];
 
+// one of the 8 sprites fetched for a line, along with the pattern bytes read for it
+#[derive(Clone, Copy, Default)]
+struct SpriteSlot {
+  x: u8,
+  attributes: u8,
+  is_sprite_zero: bool,
+  pattern_address: u16,
+  pattern_low: u8,
+  pattern_high: u8
+}
+
 pub struct PPU {
   ctrl: ControlRegister,
   mask: MaskRegister,
@@ -198,13 +208,16 @@ pub struct PPU {
   pub palette_table: [u8; 32],
   pub chr_rom: Vec<u8>,
   pub chr_ram: Vec<u8>,
+  // the console's own 2K, CIRAM
   pub vram: [u8; 2048],
+  pub cartridge_vram: Vec<u8>,
   pub oam_data: [u8; 256],
   secondary_oam: [u8; 32],
   pub oam_address: u8,
-  pub mirroring: Mirroring,
   internal_data: u8,
   cycles: u16,
+  // every PPU cycle since power on, which mappers get as M2 cycles
+  dots: u64,
   current_scanline: u16,
   pub nmi_triggered: bool,
   pub picture: Picture,
@@ -217,6 +230,8 @@ pub struct PPU {
   tile_low: u8,
   tile_high: u8,
   tile_address: u16,
+  sprite_slots: [SpriteSlot; 8],
+  sprite_count: usize,
   oam_read: u8,
   secondary_oam_address: u8,
   tile_shift_high: u16,
@@ -225,7 +240,7 @@ pub struct PPU {
 }
 
 impl PPU {
-  pub fn new(chr_rom: Vec<u8>, chr_ram: Vec<u8>, mirroring: Mirroring) -> Self {
+  pub fn new(chr_rom: Vec<u8>, chr_ram: Vec<u8>) -> Self {
     PPU {
       ctrl: ControlRegister::from_bits_truncate(0b00000000),
       mask: MaskRegister::from_bits_truncate(0b00000000),
@@ -237,22 +252,25 @@ impl PPU {
       secondary_oam: [0; 32],
       oam_address: 0,
       vram: [0; 2048],
-      mirroring,
+      cartridge_vram: Vec::new(),
       internal_data: 0,
       palette_table: [0; 32],
       cycles: 0,
+      dots: 0,
       current_scanline: 0,
       nmi_triggered: false,
       picture: Picture::new(),
       joypad: Joypad::new(),
       previous_time: 0,
-      mapper: Mapper::Empty(Empty {}),
+      mapper: Box::new(Empty::default()),
       previous_palette: 0,
       current_palette: 0,
       next_palette: 0,
       tile_low: 0,
       tile_high: 0,
       tile_address: 0,
+      sprite_slots: [SpriteSlot::default(); 8],
+      sprite_count: 0,
       oam_read: 0,
       secondary_oam_address: 0,
       tile_shift_high: 0,
@@ -265,10 +283,6 @@ impl PPU {
     if self.cycles >= CYCLES_PER_SCANLINE {
       self.cycles -= CYCLES_PER_SCANLINE;
 
-      if self.current_scanline < SCREEN_HEIGHT {
-        self.draw_sprites();
-      }
-
       self.current_scanline += 1;
       self.background_pixels_drawn = Vec::new();
 
@@ -289,12 +303,15 @@ impl PPU {
       self.cycle();
     }
     self.cycles += 1;
+    self.dots = self.dots.wrapping_add(1);
   }
 
   fn fetch_attribute_byte(&mut self) {
     let attribute_address = self.scroll.attribute_address();
 
-    let attribute_byte = self.vram[self.mirror_vram_index(attribute_address) as usize];
+    self.put_bus_address(attribute_address);
+
+    let attribute_byte = self.read_nametable(attribute_address, PpuFetch::Background);
     let shift = self.scroll.attribute_shift();
 
     self.next_palette = 1 + ((attribute_byte >> shift) & 0b11) * 4;
@@ -306,7 +323,9 @@ impl PPU {
     self.previous_palette = self.current_palette;
     self.current_palette = self.next_palette;
 
-    let tile_number = self.vram[self.mirror_vram_index(address) as usize];
+    self.put_bus_address(address);
+
+    let tile_number = self.read_nametable(address, PpuFetch::Background);
     let bank = self.ctrl.background_pattern_table_addr();
 
     self.tile_shift_low |= self.tile_low as u16;
@@ -316,91 +335,145 @@ impl PPU {
 
     self.tile_address = tile_index + self.scroll.fine_y();
   }
+  // sprites are drawn from what was fetched for this line during the previous one
   fn draw_sprites(&mut self) {
     let y = self.current_scanline;
 
-    for i in (0..self.oam_data.len()).step_by(4) {
-      let tile_y = self.oam_data[i];
-      let mut tile_number = self.oam_data[i+1];
-      let attributes = self.oam_data[i+2];
-      let tile_x = self.oam_data[i+3];
+    for index in 0..self.sprite_count {
+      let slot = self.sprite_slots[index];
 
-      let y_flip = (attributes >> 7) & 0b1 == 1;
-      let x_flip = (attributes >> 6) & 0b1 == 1;
+      let x_flip = (slot.attributes >> 6) & 0b1 == 1;
 
-      let sprite_behind_background = (attributes >> 5) & 0b1 == 1;
+      let sprite_behind_background = (slot.attributes >> 5) & 0b1 == 1;
 
-      let mut y_pos_in_tile: i16 = (y as i16) - (tile_y as i16);
+      let palette_index = slot.attributes & 0b11;
 
-      if y_flip {
-        y_pos_in_tile = self.ctrl.sprite_size() as i16 - 1 - y_pos_in_tile;
-      }
+      let sprite_palettes = self.get_sprite_palette(palette_index);
 
-      if y_pos_in_tile >= 0 && (y_pos_in_tile as u16) < self.ctrl.sprite_size() as u16 {
-        let palette_index = attributes & 0b11;
+      for x in 0..8 {
+        let bit_pos = if x_flip {
+          x
+        } else {
+          7 - x
+        };
 
-        let sprite_palettes = self.get_sprite_palette(palette_index);
+        let color_index = ((slot.pattern_low >> bit_pos) & 0b1) + (((slot.pattern_high >> bit_pos) & 0b1) << 1);
 
+        let rgb = match color_index {
+          0 => continue,
+          _ => PALETTE_TABLE[sprite_palettes[color_index as usize] as usize]
+        };
 
-        let mut y_index = y_pos_in_tile;
+        let x_pos = slot.x as usize + x as usize;
 
-        let bank = if self.ctrl.sprite_size() == 8 {
-          self.ctrl.sprite_pattern_table_address()
-        } else {
-          let bank: u16 = if tile_number & 0b1 == 0 { 0 } else { 0x1000 };
-          tile_number = tile_number & 0b11111110;
+        if x_pos >= SCREEN_WIDTH as usize {
+          continue;
+        }
 
-          if y_pos_in_tile > 7 {
-            y_index += 8;
-          }
+        if slot.is_sprite_zero
+          && x_pos != 255
+          && self.rendering_enabled()
+          && !self.status.contains(StatusRegister::SPRITE_ZERO_HIT) {
+          self.status.set(StatusRegister::SPRITE_ZERO_HIT, true);
+        }
 
-          bank
-        };
+        let is_pixel_visible = !(sprite_behind_background && self.background_pixels_drawn[x_pos]);
 
-        let tile_index = bank + tile_number as u16 * 16;
+        if is_pixel_visible {
+          self.picture.set_pixel(x_pos, y as usize, rgb);
+        }
+      }
+    }
 
-        let lower_byte = self.read_chr(tile_index + y_index as u16);
-        let upper_byte = self.read_chr(tile_index + y_index as u16 + 8);
+    // the slots are refilled at dot 257, which doesn't happen with rendering off
+    self.sprite_count = 0;
+  }
 
-        for x in 0..8 {
-          let bit_pos = if x_flip {
-            x
-          } else {
-            7 - x
-          };
+  // the address of one row of a sprite's pattern, flipped vertically if it needs to be.
+  // 8x16 sprites pick their pattern table with bit 0 of the tile number
+  fn sprite_pattern_address(&self, tile_number: u8, attributes: u8, row: i16) -> u16 {
+    let sprite_size = self.ctrl.sprite_size() as i16;
+    let row = if (attributes >> 7) & 0b1 == 1 { sprite_size - 1 - row } else { row } as u16;
 
-          let color_index = ((lower_byte >> bit_pos) & 0b1) + (((upper_byte >> bit_pos) & 0b1) << 1);
+    if sprite_size == 8 {
+      self.ctrl.sprite_pattern_table_address() + tile_number as u16 * 16 + row
+    } else {
+      let bank: u16 = if tile_number & 0b1 == 0 { 0 } else { 0x1000 };
 
-          let rgb = match color_index {
-            0 => continue,
-            _ => PALETTE_TABLE[sprite_palettes[color_index as usize] as usize]
-          };
+      // the bottom half is the next tile
+      bank + (tile_number & 0b11111110) as u16 * 16 + (row & 0b111) + (row & 0b1000) * 2
+    }
+  }
 
-          let x_pos = (tile_x as usize + x) as usize;
+  // the first 8 sprites on the next line get their patterns fetched between dots 257 and 320,
+  // and the empty slots fetch tile $ff instead
+  // see https://www.nesdev.org/wiki/PPU_sprite_evaluation
+  fn evaluate_sprite_fetches(&mut self) {
+    let dummy_slot = SpriteSlot {
+      pattern_address: self.sprite_pattern_address(0xff, 0, 0),
+      ..Default::default()
+    };
 
-          if x_pos >= SCREEN_WIDTH as usize || x_pos < 0 as usize {
-            continue;
-          }
+    self.sprite_slots = [dummy_slot; 8];
+    self.sprite_count = 0;
 
-          if i == 0
-            && color_index != 0
-            && x != 255
-            && self.rendering_enabled()
-            && !self.status.contains(StatusRegister::SPRITE_ZERO_HIT) {
-            self.status.set(StatusRegister::SPRITE_ZERO_HIT, true);
-          }
+    let next_scanline = if self.current_scanline == PRERENDER_SCANLINE { 0 } else { self.current_scanline + 1 };
 
-          let is_pixel_visible = !(sprite_behind_background && self.background_pixels_drawn[x_pos]);
+    if next_scanline >= SCREEN_HEIGHT {
+      return;
+    }
 
-          if is_pixel_visible {
-            self.picture.set_pixel(x_pos, y as usize, rgb);
-          }
-        }
+    let y = next_scanline as i16;
+    let sprite_size = self.ctrl.sprite_size() as i16;
+
+    let sprites: Vec<SpriteSlot> = self.oam_data
+      .chunks(4)
+      .enumerate()
+      .filter(|(_, sprite)| (0..sprite_size).contains(&(y - sprite[0] as i16)))
+      .take(8)
+      .map(|(index, sprite)| SpriteSlot {
+        x: sprite[3],
+        attributes: sprite[2],
+        is_sprite_zero: index == 0,
+        pattern_address: self.sprite_pattern_address(sprite[1], sprite[2], y - sprite[0] as i16),
+        ..Default::default()
+      })
+      .collect();
+
+    self.sprite_slots[..sprites.len()].copy_from_slice(&sprites);
+    self.sprite_count = sprites.len();
+  }
 
+  fn fetch_sprite_slot(&mut self) {
+    let dot = self.cycles - 257;
+    let slot = (dot / 8) as usize;
+    let address = self.sprite_slots[slot].pattern_address;
+
+    match dot % 8 {
+      // two garbage nametable fetches come first
+      0 | 2 => self.put_bus_address(self.scroll.tile_address()),
+      4 => {
+        self.put_bus_address(address);
+        self.sprite_slots[slot].pattern_low = self.read_chr(address, PpuFetch::Sprite);
+      }
+      6 => {
+        self.put_bus_address(address + 8);
+        self.sprite_slots[slot].pattern_high = self.read_chr(address + 8, PpuFetch::Sprite);
       }
+      _ => ()
     }
   }
 
+  fn fetch_background_chr(&mut self, address: u16) -> u8 {
+    self.put_bus_address(address);
+
+    self.read_chr(address, PpuFetch::Background)
+  }
+
+  fn put_bus_address(&mut self, address: u16) {
+    self.mapper.ppu_bus_address(address, self.dots / 3);
+  }
+
   fn cycle(&mut self) {
     if self.rendering_enabled() {
       if self.current_scanline < SCREEN_HEIGHT || self.current_scanline == PRERENDER_SCANLINE {
@@ -409,8 +482,8 @@ impl PPU {
           match self.cycles % 8 {
             1 => self.fetch_nametable_byte(),
             3 => self.fetch_attribute_byte(),
-            5 => self.tile_low = self.read_chr(self.tile_address),
-            7 => self.tile_high = self.read_chr(self.tile_address + 8),
+            5 => self.tile_low = self.fetch_background_chr(self.tile_address),
+            7 => self.tile_high = self.fetch_background_chr(self.tile_address + 8),
             _ => ()
           }
 
@@ -423,11 +496,18 @@ impl PPU {
 
         match self.cycles {
           256 => self.scroll.increment_y(),
-          257 => self.scroll.copy_x(),
+          257 => {
+            self.scroll.copy_x();
+            self.evaluate_sprite_fetches();
+          }
           280..=304 if self.current_scanline == PRERENDER_SCANLINE => self.scroll.copy_y(),
           _ => ()
         }
 
+        if matches!(self.cycles, 257..=320) {
+          self.fetch_sprite_slot();
+        }
+
         if matches!(self.cycles, 321..=340) {
           // sprite dummy cycle...
           self.oam_read = self.secondary_oam[0];
@@ -438,6 +518,10 @@ impl PPU {
     // finally render the pixel
     if matches!(self.cycles, 1..=256) && self.current_scanline < SCREEN_HEIGHT {
       self.draw_pixel();
+
+      if self.cycles == 256 {
+        self.draw_sprites();
+      }
     }
     if matches!(self.cycles, 1..=256) || matches!(self.cycles, 321..=336) {
       self.tile_shift_high <<= 1;
@@ -476,12 +560,6 @@ impl PPU {
     self.picture.set_pixel(x as usize, y as usize, rgb);
   }
 
-  pub fn update_mirroring(&mut self) {
-    if !matches!(self.mapper, Mapper::Empty(_)) {
-      self.mirroring = self.mapper.mirroring()
-    }
-  }
-
   pub fn cap_fps(&mut self) {
     let current_time = SystemTime::now()
       .duration_since(UNIX_EPOCH)
@@ -502,25 +580,50 @@ impl PPU {
       .as_millis();
   }
 
+  // $2000-$2fff (and its mirror up to $3eff) is four 1K slots, each wired wherever the mapper says
   // see https://www.nesdev.org/wiki/Mirroring
-  fn mirror_vram_index(&self, address: u16) -> u16 {
-    let mirrored_address = address & 0b10111111111111; // mirror down address to range 0x2000 to 0x2eff, where nametables exist
-    let vram_index = mirrored_address - 0x2000;
-    let name_table_index = vram_index / 0x400; // this should give us a value between 0-3 which points to what nametable is being referred to
-
-    match (&self.mirroring, name_table_index) {
-      (Mirroring::SingleScreenA, 3) => vram_index - 0xc00,
-      (Mirroring::SingleScreenB, 0) => vram_index + 0x400,
-      (Mirroring::Horizontal, 1)
-        | (Mirroring::Horizontal, 2)
-        | (Mirroring::SingleScreenA, 1)
-        | (Mirroring::SingleScreenB, 2) => vram_index - 0x400, // for horizontal, 1 is in first kb of memory, 2 is in 2nd kb of memory.
-      (Mirroring::Vertical, 2)
-        | (Mirroring::Vertical, 3)
-        | (Mirroring::Horizontal, 3)
-        | (Mirroring::SingleScreenB, 3)
-        | (Mirroring::SingleScreenA, 2) => vram_index- 0x800, // 2 is in first kb of memory 3 is in 2nd for vertical. 3 is in 2nd for horizontal.
-      _ => vram_index // either it's four screen which has no mirroring or it's nametable 0 or another nametable that doesn't need the offset
+  fn nametable_slot(&self, address: u16) -> (Nametable, usize) {
+    let slot = ((address >> 10) & 0b11) as usize;
+
+    (self.mapper.nametable(slot), (address & 0x3ff) as usize)
+  }
+
+  fn read_vram(&self, address: u16) -> u8 {
+    let (nametable, offset) = self.nametable_slot(address);
+
+    self.nametable_byte(nametable, offset)
+  }
+
+  fn nametable_byte(&self, nametable: Nametable, offset: usize) -> u8 {
+    match nametable {
+      Nametable::CiramA => self.vram[offset],
+      Nametable::CiramB => self.vram[0x400 + offset],
+      Nametable::CartridgeVram(page) => (page * 0x400 + offset)
+        .checked_rem(self.cartridge_vram.len())
+        .map_or(0, |index| self.cartridge_vram[index]),
+      Nametable::ChrRom(page) => (page * 0x400 + offset)
+        .checked_rem(self.chr_rom.len())
+        .map_or(0, |index| self.chr_rom[index]),
+      Nametable::Fill { tile, palette } => if offset >= 0x3c0 { (palette & 0b11) * 0b01010101 } else { tile }
+    }
+  }
+
+  fn write_vram(&mut self, address: u16, value: u8) {
+    let (nametable, offset) = self.nametable_slot(address);
+
+    self.write_nametable_byte(nametable, offset, value);
+  }
+
+  fn write_nametable_byte(&mut self, nametable: Nametable, offset: usize, value: u8) {
+    match nametable {
+      Nametable::CiramA => self.vram[offset] = value,
+      Nametable::CiramB => self.vram[0x400 + offset] = value,
+      Nametable::CartridgeVram(page) => {
+        if let Some(index) = (page * 0x400 + offset).checked_rem(self.cartridge_vram.len()) {
+          self.cartridge_vram[index] = value;
+        }
+      }
+      Nametable::ChrRom(_) | Nametable::Fill { .. } => ()
     }
   }
 
@@ -533,23 +636,32 @@ impl PPU {
     data
   }
 
-  fn read_chr(&mut self, address: u16) -> u8 {
+  fn read_nametable(&mut self, address: u16, fetch: PpuFetch) -> u8 {
+    if let Some(val) = self.mapper.read_nametable(address, fetch) {
+      return val;
+    }
+
+    self.read_vram(address)
+  }
 
-    let chr = if !self.chr_rom.is_empty() {
-      &self.chr_rom
+  fn read_chr(&mut self, address: u16, fetch: PpuFetch) -> u8 {
+    if let Some(nametable) = self.mapper.pattern_nametable(address) {
+      return self.nametable_byte(nametable, (address & 0x3ff) as usize);
+    }
+
+    if let Some(mapped_address) = self.mapper.read_chr(address, fetch) {
+      self.chr_byte(mapped_address)
     } else {
-      &self.chr_ram
-    };
+      0
+    }
+  }
 
-    match &mut self.mapper {
-      Mapper::Empty(_) => chr[address as usize],
-      _ => {
-        if let Some(mapped_address) = self.mapper.mem_read(address) {
-          chr[mapped_address]
-        } else {
-          0
-        }
-      }
+  // boards with both CHR-ROM and CHR-RAM map the RAM in right after the ROM
+  fn chr_byte(&self, mapped_address: usize) -> u8 {
+    if mapped_address < self.chr_rom.len() {
+      self.chr_rom[mapped_address]
+    } else {
+      self.chr_ram.get(mapped_address - self.chr_rom.len()).copied().unwrap_or(0)
     }
   }
 
@@ -606,18 +718,36 @@ impl PPU {
 
   pub fn write_to_ppu_address(&mut self, value: u8) {
     self.scroll.set_address(value);
+
+    // the second write puts the new address on the bus
+    if !self.scroll.latch {
+      self.put_bus_address(self.scroll.get_address());
+    }
   }
 
   pub fn write_to_data(&mut self, value: u8) {
     let address = self.scroll.get_address();
 
+    self.put_bus_address(address);
+
     match address {
       0x0000 ..= 0x1fff => {
-        if !self.chr_ram.is_empty() {
-          self.chr_ram[address as usize] = value;
+        if let Some(nametable) = self.mapper.pattern_nametable(address) {
+          self.write_nametable_byte(nametable, (address & 0x3ff) as usize, value);
+        } else {
+          // CHR-RAM is banked the same way it's read, after any CHR-ROM
+          let mapped_address = self.mapper.mem_read(address).unwrap_or(address as usize);
+
+          if let Some(byte) = mapped_address.checked_sub(self.chr_rom.len()).and_then(|offset| self.chr_ram.get_mut(offset)) {
+            *byte = value;
+          }
         }
       },
-      0x2000 ..=0x2fff => self.vram[self.mirror_vram_index(address) as usize] = value,
+      0x2000 ..=0x2fff => {
+        if !self.mapper.write_nametable(address, value) {
+          self.write_vram(address, value);
+        }
+      }
       0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
         let address_mirror = address - 0x10;
         self.palette_table[((address_mirror - 0x3f00) % self.palette_table.len() as u16) as usize] = value;
@@ -626,8 +756,6 @@ impl PPU {
       _ => panic!("shouldn't get here")
     }
 
-    self.mapper.ppu_bus_write(address, value);
-
     self.increment_address(self.ctrl.vram_address_increment());
   }
 
@@ -647,38 +775,80 @@ impl PPU {
   pub fn read_data(&mut self) -> u8 {
     let address = self.scroll.get_address();
 
+    self.put_bus_address(address);
+
     self.increment_address(self.ctrl.vram_address_increment());
 
     match address {
       0x0000 ..= 0x1fff => {
         let result = self.internal_data;
 
-        self.internal_data = self.read_chr(address);
+        self.internal_data = self.read_chr(address, PpuFetch::Data);
 
         result
       },
       0x2000 ..= 0x2fff => {
         let result = self.internal_data;
 
-        self.internal_data = self.vram[self.mirror_vram_index(address) as usize];
+        self.internal_data = self.read_nametable(address, PpuFetch::Data);
 
         result
       }
       0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
         let address_mirror = address - 0x10;
 
-        self.internal_data = self.vram[self.mirror_vram_index(address - 0x1000) as usize];
+        self.internal_data = self.read_vram(address - 0x1000);
 
         self.palette_table[((address_mirror - 0x3f00) % self.palette_table.len() as u16) as usize]
       }
 
       0x3f00..=0x3fff =>
       {
-        self.internal_data = self.vram[self.mirror_vram_index(address - 0x1000) as usize];
+        self.internal_data = self.read_vram(address - 0x1000);
 
         self.palette_table[((address - 0x3f00) % self.palette_table.len() as u16) as usize]
       }
       _ => panic!("shouldn't get here")
     }
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{PPU, PALETTE_TABLE, SCREEN_WIDTH};
+
+  fn pixel(ppu: &PPU, x: usize, y: usize) -> (u8, u8, u8) {
+    let i = (x + y * SCREEN_WIDTH as usize) * 3;
+
+    (ppu.picture.data[i], ppu.picture.data[i + 1], ppu.picture.data[i + 2])
+  }
+
+  fn run_until(ppu: &mut PPU, scanline: u16, dot: u16) {
+    while ppu.current_scanline != scanline || ppu.cycles != dot {
+      ppu.tick();
+    }
+  }
+
+  #[test]
+  fn draws_sprites_from_patterns_fetched_on_the_line_before() {
+    let mut chr_rom = vec![0; 8192];
+    // tile 1's top row is a single pixel of color 1
+    chr_rom[16] = 0x80;
+
+    let mut ppu = PPU::new(chr_rom, Vec::new());
+
+    ppu.oam_data[..4].copy_from_slice(&[10, 1, 0, 20]);
+    ppu.palette_table[0x11] = 0x30;
+    ppu.write_to_mask(0b0001_1000);
+
+    run_until(&mut ppu, 9, 321);
+
+    // the fetch is done, so changing the pattern now can't reach this line's sprites
+    ppu.chr_rom[16] = 0;
+
+    run_until(&mut ppu, 11, 1);
+
+    assert_eq!(pixel(&ppu, 20, 10), PALETTE_TABLE[0x30]);
+    assert_eq!(pixel(&ppu, 21, 10), PALETTE_TABLE[0]);
+  }
+}
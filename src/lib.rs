@@ -3,6 +3,8 @@
 pub mod cpu;
 pub mod cartridge;
 pub mod mapper;
+pub mod patch;
+pub mod player;
 
 #[macro_use]
 extern crate bitflags;
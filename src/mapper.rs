@@ -2,26 +2,82 @@ pub mod sxrom;
 pub mod uxrom;
 pub mod cnrom;
 pub mod txrom;
+pub mod fds;
+pub mod nsf;
+pub mod mmc5;
+pub mod axrom;
+pub mod mmc2;
+pub mod vrc_irq;
+pub mod vrc4;
+pub mod vrc6;
+pub mod opll;
+pub mod vrc7;
+pub mod namco163;
+pub mod sunsoft5b;
+pub mod fme7;
+pub mod eeprom;
+pub mod bandai;
+pub mod gxrom;
+pub mod color_dreams;
+pub mod bnrom;
+pub mod nina001;
+pub mod nina06;
+pub mod camerica;
+pub mod namco108;
+pub mod action53;
+pub mod multicart;
+pub mod registry;
+
+pub use registry::{MapperBoard, MapperFactory, MapperRegistry};
+
+use crate::cartridge::{Cartridge, Mirroring};
+
+// whichever board the cartridge has, picked through the MapperRegistry. It has to be
+// Send since the desktop app's audio callback runs on its own thread.
+pub type Mapper = Box<dyn MapperActions + Send>;
 
-use sxrom::Sxrom;
-use uxrom::Uxrom;
-use cnrom::Cnrom;
-use txrom::Txrom;
+pub enum BankType {
+  Chr,
+  Prg
+}
 
-use crate::cartridge::Mirroring;
+// what the PPU is reading for, so boards can tell sprite and background fetches apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PpuFetch {
+  Background,
+  Sprite,
+  Data
+}
 
-pub enum Mapper {
-  Empty(Empty),
-  Sxrom(Sxrom),
-  Uxrom(Uxrom),
-  Cnrom(Cnrom),
-  Txrom(Txrom)
+// what one of the four 1K nametable slots at $2000-$2fff is wired to
+// see https://www.nesdev.org/wiki/Mirroring#Nametable_Mirroring
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nametable {
+  // either half of the console's 2K of VRAM
+  CiramA,
+  CiramB,
+  // a 1K page of RAM on the cartridge, like the extra 2K four-screen boards carry
+  CartridgeVram(usize),
+  // a 1K page of CHR-ROM, read only
+  ChrRom(usize),
+  // every tile reads the same byte and every attribute the same palette
+  Fill { tile: u8, palette: u8 }
 }
 
-pub enum BankType {
-  Chr,
-  Prg
+impl Nametable {
+  pub fn from_mirroring(mirroring: Mirroring, slot: usize) -> Self {
+    match (mirroring, slot & 0b11) {
+      (Mirroring::SingleScreenA, _)
+        | (Mirroring::Horizontal, 0 | 1)
+        | (Mirroring::Vertical, 0 | 2)
+        | (Mirroring::FourScreen, 0) => Nametable::CiramA,
+      (Mirroring::FourScreen, 1) => Nametable::CiramB,
+      (Mirroring::FourScreen, slot) => Nametable::CartridgeVram(slot - 2),
+      _ => Nametable::CiramB
+    }
+  }
 }
+
 pub trait MapperActions {
   fn mem_read(&mut self, _address: u16) -> Option<usize> {
     None
@@ -31,7 +87,7 @@ pub trait MapperActions {
     None
   }
 
-  fn tick(&mut self, _cycles: u8) {
+  fn tick(&mut self, _cycles: u16) {
 
   }
 
@@ -39,8 +95,43 @@ pub trait MapperActions {
     Mirroring::SingleScreenA
   }
 
-  fn ppu_bus_write(&mut self, _address: u16, _val: u8) {
+  // where each nametable slot points, asked on every nametable access. Boards with
+  // a standard layout only need to report it through mirroring
+  fn nametable(&self, slot: usize) -> Nametable {
+    Nametable::from_mirroring(self.mirroring(), slot)
+  }
 
+  // every address the PPU puts on its bus, from rendering fetches as well as $2006/$2007,
+  // with the CPU cycle it happened on so boards watching A12 can time how long it was low
+  fn ppu_bus_address(&mut self, _address: u16, _m2_cycle: u64) {
+
+  }
+
+  // every pattern table read the PPU makes comes through here, so boards that
+  // react to what's being fetched can watch it as well as translate it
+  fn read_chr(&mut self, address: u16, _fetch: PpuFetch) -> Option<usize> {
+    self.mem_read(address)
+  }
+
+  // lets a board put one of the console's nametables in the pattern tables instead of CHR,
+  // asked ahead of read_chr for every 1K pattern table bank
+  fn pattern_nametable(&self, _address: u16) -> Option<Nametable> {
+    None
+  }
+
+  // lets a mapper supply nametable bytes itself ahead of the slot it maps, for
+  // boards that change what's read depending on the fetch, like the MMC5
+  fn read_nametable(&mut self, _address: u16, _fetch: PpuFetch) -> Option<u8> {
+    None
+  }
+
+  fn write_nametable(&mut self, _address: u16, _val: u8) -> bool {
+    false
+  }
+
+  // expansion audio, in the same units as APU::get_sample
+  fn audio_output(&self) -> f32 {
+    0.0
   }
 
   fn irq_pending(&self) -> bool {
@@ -50,67 +141,111 @@ pub trait MapperActions {
   fn set_irq_pending(&mut self, _val: bool) {
 
   }
-}
 
-impl MapperActions for Mapper {
-  fn mem_read(&mut self, address: u16) -> Option<usize> {
-    match self {
-      Mapper::Empty(_) => None,
-      Mapper::Sxrom(sxrom) => sxrom.mem_read(address),
-      Mapper::Uxrom(uxrom) => uxrom.mem_read(address),
-      Mapper::Cnrom(cnrom) => cnrom.mem_read(address),
-      Mapper::Txrom(txrom) => txrom.mem_read(address)
-    }
+  // lets a mapper drive the data bus itself instead of translating the address
+  // into PRG-ROM or PRG-RAM, e.g. for registers and RAM that live on the board.
+  // Reads from $4020 up and writes from $2000 up come here first, and whatever
+  // isn't claimed in the expansion area at $4020-$5fff is open bus
+  fn cpu_read(&mut self, _address: u16) -> Option<u8> {
+    None
   }
 
-  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
-    match self {
-      Mapper::Empty(_) => None,
-      Mapper::Sxrom(sxrom) => sxrom.mem_write(address, val),
-      Mapper::Uxrom(uxrom) => uxrom.mem_write(address, val),
-      Mapper::Cnrom(cnrom) => cnrom.mem_write(address, val),
-      Mapper::Txrom(txrom) => txrom.mem_write(address, val)
-    }
+  fn cpu_write(&mut self, _address: u16, _val: u8) -> bool {
+    false
   }
 
-  fn tick(&mut self, cycles: u8) {
-    match self {
-      Mapper::Sxrom(sxrom) => sxrom.tick(cycles),
-      _ => ()
-    }
+  // battery backed data kept by the mapper itself, saved after PRG-RAM in the save file
+  fn save_pending(&self) -> bool {
+    false
   }
 
-  fn mirroring(&self) -> Mirroring {
-    match self {
-      Mapper::Sxrom(sxrom) => sxrom.mirroring(),
-      Mapper::Uxrom(uxrom) => uxrom.mirroring(),
-      Mapper::Cnrom(cnrom) => cnrom.mirroring(),
-      Mapper::Txrom(txrom) => txrom.mirroring(),
-      _ => panic!("mapper not supported")
-    }
+  fn save_data(&mut self) -> Option<Vec<u8>> {
+    None
+  }
+
+  fn load_save_data(&mut self, _data: &[u8]) {
+
+  }
+
+  // the console's reset button, which some boards are wired to see
+  fn reset(&mut self) {
+
+  }
+
+  fn disk_sides(&self) -> usize {
+    0
   }
 
-  fn ppu_bus_write(&mut self, address: u16, _val: u8) {
-    match self {
-      Mapper::Txrom(txrom) => txrom.ppu_bus_write(address, _val),
-      _ => ()
+  fn current_disk_side(&self) -> Option<usize> {
+    None
+  }
+
+  fn insert_disk(&mut self, _side: usize) {
+
+  }
+
+  fn eject_disk(&mut self) {
+
+  }
+}
+
+// mapper 0 has no registers, but boards like Family BASIC and trainer ROMs
+// still expect PRG-RAM at $6000-$7fff. It also stands in before a cartridge is loaded.
+#[derive(Default)]
+pub struct Empty {
+  prg_rom_size: usize,
+  mirroring: Mirroring
+}
+
+impl Empty {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    Self {
+      prg_rom_size: cartridge.prg_rom.len(),
+      mirroring: cartridge.mirroring
     }
   }
-  fn irq_pending(&self) -> bool {
-    match self {
-      Mapper::Txrom(txrom) => txrom.irq_pending(),
-      _ => false
+}
+
+impl MapperActions for Empty {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(address as usize),
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      // NROM-128 mirrors its 16K into both halves
+      0x8000..=0xffff if self.prg_rom_size > 0 => Some((address - 0x8000) as usize % self.prg_rom_size),
+      _ => None
     }
   }
 
-  fn set_irq_pending(&mut self, val: bool) {
-    match self {
-      Mapper::Txrom(txrom) => txrom.set_irq_pending(val),
-      _ => ()
+  fn mem_write(&mut self, address: u16, _val: u8) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      _ => None
     }
   }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Empty {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(0, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Empty::load(cartridge))
+  }
 }
 
-pub struct Empty { }
+// a blank NES 2.0 image for the given board, so mapper tests can go through Cartridge::new
+#[cfg(test)]
+pub fn test_cartridge(mapper: u16, submapper: u8, prg_rom_banks: u8, chr_rom_banks: u8) -> Cartridge {
+  let mut rom = vec![0x4e, 0x45, 0x53, 0x1a, prg_rom_banks, chr_rom_banks];
 
-impl MapperActions for Empty { }
\ No newline at end of file
+  rom.push(((mapper & 0x0f) as u8) << 4);
+  rom.push((mapper & 0xf0) as u8 | 0b1000);
+  rom.push((submapper << 4) | (mapper >> 8) as u8);
+  rom.resize(16 + prg_rom_banks as usize * 16_384 + chr_rom_banks as usize * 8192, 0);
+
+  Cartridge::new(rom, None).unwrap()
+}
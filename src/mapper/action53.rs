@@ -0,0 +1,172 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 16_384;
+const CHR_BANK_SIZE: usize = 8192;
+
+const CHR_RAM_SIZE: usize = 32_768;
+
+// the homebrew Action 53 compilations' mapper, which can play NROM, CNROM, UNROM, BNROM
+// and AOROM games side by side. $5000-$5fff picks one of four registers through bits 7
+// and 0, and writes to $8000-$ffff go to the selected one:
+//   $00: CHR-RAM bank, $01: inner PRG bank, $80: mode, $81: outer 32K PRG bank
+// see https://www.nesdev.org/wiki/Action_53_mapper
+pub struct Action53 {
+  register_select: u8,
+  chr_bank: usize,
+  inner_bank: usize,
+  mode: u8,
+  outer_bank: usize,
+  prg_page_size: usize
+}
+
+impl Action53 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    let mut action53 = Self {
+      register_select: 0,
+      chr_bank: 0,
+      inner_bank: 0,
+      mode: 0,
+      outer_bank: 0,
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1)
+    };
+
+    action53.reset();
+
+    action53
+  }
+
+  // games on 1 screen boards pick their nametable from bit 4 of the CHR or PRG bank
+  fn write_single_screen(&mut self, val: u8) {
+    if self.mode & 0b10 == 0 {
+      self.mode = (self.mode & !0b1) | ((val >> 4) & 0b1);
+    }
+  }
+
+  fn prg_bank(&self, address: u16) -> usize {
+    let high_half = address >= 0xc000;
+    let outer = self.outer_bank << 1;
+
+    let inner = match (self.mode >> 2) & 0b11 {
+      0 | 1 => (self.inner_bank << 1) | high_half as usize,
+      // the fixed half is the first or last 16K of the outer bank
+      2 if !high_half => return outer,
+      3 if high_half => return outer | 1,
+      _ => self.inner_bank
+    };
+
+    // the game size decides how many of the low bank bits come from the inner bank
+    let inner_mask = (2 << ((self.mode >> 4) & 0b11)) - 1;
+
+    (outer & !inner_mask) | (inner & inner_mask)
+  }
+}
+
+impl MapperActions for Action53 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_bank * CHR_BANK_SIZE + address as usize),
+      0x8000..=0xffff => Some((self.prg_bank(address) % self.prg_page_size) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1))),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if address < 0x8000 {
+      return None;
+    }
+
+    match self.register_select {
+      0x00 => {
+        self.chr_bank = (val & 0b11) as usize;
+        self.write_single_screen(val);
+      }
+      0x01 => {
+        self.inner_bank = (val & 0x0f) as usize;
+        self.write_single_screen(val);
+      }
+      0x80 => self.mode = val & 0x3f,
+      _ => self.outer_bank = val as usize
+    }
+
+    None
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    if !(0x5000..=0x5fff).contains(&address) {
+      return false;
+    }
+
+    self.register_select = val & 0x81;
+
+    true
+  }
+
+  // the menu sits in the last 32K, and a reset puts it back there
+  fn reset(&mut self) {
+    self.outer_bank = 0xff;
+    self.mode = 0;
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    match self.mode & 0b11 {
+      0 => Mirroring::SingleScreenA,
+      1 => Mirroring::SingleScreenB,
+      2 => Mirroring::Vertical,
+      _ => Mirroring::Horizontal
+    }
+  }
+}
+
+impl MapperBoard for Action53 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(28, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Action53::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::{test_cartridge, Mapper};
+
+  fn write_register(mapper: &mut Mapper, register: u8, val: u8) {
+    mapper.cpu_write(0x5000, register);
+    mapper.mem_write(0x8000, val);
+  }
+
+  #[test]
+  fn starts_and_resets_into_the_last_bank() {
+    let mut mapper = test_cartridge(28, 0, 32, 0).mapper;
+
+    assert_eq!(mapper.mem_read(0x8000), Some(30 * 0x4000));
+    assert_eq!(mapper.mem_read(0xc000), Some(31 * 0x4000));
+
+    write_register(&mut mapper, 0x81, 2);
+    assert_eq!(mapper.mem_read(0x8000), Some(4 * 0x4000));
+
+    mapper.reset();
+    assert_eq!(mapper.mem_read(0x8000), Some(30 * 0x4000));
+  }
+
+  #[test]
+  fn switches_unrom_banks_inside_the_outer_bank() {
+    let mut mapper = test_cartridge(28, 0, 32, 0).mapper;
+
+    // UNROM with $c000 fixed, in a 128K game starting at the second 128K block
+    write_register(&mut mapper, 0x80, 0b10_11_10);
+    write_register(&mut mapper, 0x81, 4);
+    write_register(&mut mapper, 0x01, 5);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(13 * 0x4000));
+    assert_eq!(mapper.mem_read(0xc000), Some(9 * 0x4000));
+
+    write_register(&mut mapper, 0x00, 3);
+    assert_eq!(mapper.mem_read(0x0010), Some(3 * 0x2000 + 0x10));
+  }
+}
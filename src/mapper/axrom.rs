@@ -0,0 +1,64 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 32_768;
+const CHR_RAM_SIZE: usize = 8192;
+
+// see https://www.nesdev.org/wiki/AxROM
+pub struct Axrom {
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Axrom {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    Self {
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      mirroring: Mirroring::SingleScreenA
+    }
+  }
+}
+
+impl MapperActions for Axrom {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(address as usize),
+      0x8000..=0xffff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if let 0x8000..=0xffff = address {
+      self.prg_rom_bank = ((val & 0b111) as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE;
+
+      // bit 4 picks which nametable fills the screen
+      self.mirroring = if (val >> 4) & 0b1 == 0 {
+        Mirroring::SingleScreenA
+      } else {
+        Mirroring::SingleScreenB
+      };
+    }
+
+    None
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Axrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(7, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Axrom::load(cartridge))
+  }
+}
@@ -0,0 +1,232 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+use super::eeprom::{Eeprom, EepromChip};
+
+const PRG_ROM_BANK_SIZE: usize = 16_384;
+const CHR_BANK_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+const CHR_RAM_SIZE: usize = 8192;
+
+// Bandai's FCG-1/2 and LZ93D50 boards. The older FCG chips take their registers at
+// $6000-$7fff and load the IRQ counter directly, the LZ93D50 moved them to $8000-$ffff,
+// latches the counter and can talk to a serial EEPROM.
+//   16: FCG (submapper 4) or LZ93D50 with a 24C02 (submapper 5), submapper 0 decodes both
+//  153: LZ93D50 with 8K of PRG-RAM and a 512K PRG-ROM picked by the CHR registers
+//  157: LZ93D50 with a 24C02 and CHR-RAM (Datach, without the barcode reader)
+//  159: LZ93D50 with a 24C01
+// see https://www.nesdev.org/wiki/Bandai_FCG_board
+pub struct Bandai {
+  registers_at_6000: bool,
+  registers_at_8000: bool,
+  latches_irq: bool,
+  has_prg_ram: bool,
+  prg_rom_bank: usize,
+  prg_page_size: usize,
+  chr_registers: [u8; 8],
+  chr_page_size: usize,
+  chr_ram: bool,
+  mirroring: Mirroring,
+  irq_enabled: bool,
+  irq_latch: u16,
+  irq_counter: u16,
+  irq_pending: bool,
+  prg_ram_enabled: bool,
+  eeprom: Option<Eeprom>
+}
+
+impl Bandai {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    let mapper = cartridge.header.mapper;
+    let submapper = cartridge.header.submapper;
+
+    let is_fcg = mapper == 16 && submapper == 4;
+    let has_prg_ram = mapper == 153;
+
+    let eeprom = match mapper {
+      16 if !is_fcg => Some(Eeprom::new(EepromChip::C24C02)),
+      157 => Some(Eeprom::new(EepromChip::C24C02)),
+      159 => Some(Eeprom::new(EepromChip::C24C01)),
+      _ => None
+    };
+
+    if has_prg_ram && cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_ram = cartridge.chr_rom.is_empty();
+
+    if chr_ram && cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    Self {
+      registers_at_6000: mapper == 16 && (submapper == 0 || is_fcg),
+      registers_at_8000: !is_fcg,
+      latches_irq: !is_fcg,
+      has_prg_ram,
+      prg_rom_bank: 0,
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_registers: [0; 8],
+      chr_page_size: (cartridge.chr_rom.len() / CHR_BANK_SIZE).max(1),
+      chr_ram,
+      mirroring: cartridge.mirroring,
+      irq_enabled: false,
+      irq_latch: 0,
+      irq_counter: 0,
+      irq_pending: false,
+      prg_ram_enabled: false,
+      eeprom
+    }
+  }
+
+  // on 153 boards bit 0 of any CHR register picks which 256K half of PRG-ROM is used
+  fn outer_prg_bank(&self) -> usize {
+    if self.has_prg_ram {
+      self.chr_registers.iter().fold(0, |bank, register| bank | (register & 0b1) as usize) << 4
+    } else {
+      0
+    }
+  }
+
+  fn translate_prg_address(&self, address: u16) -> usize {
+    let bank = match address {
+      0x8000..=0xbfff => self.outer_prg_bank() | self.prg_rom_bank,
+      _ => self.outer_prg_bank() | 0x0f
+    };
+
+    (bank % self.prg_page_size) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1))
+  }
+
+  fn write_register(&mut self, address: u16, val: u8) {
+    match address & 0x0f {
+      register @ 0..=7 => self.chr_registers[register as usize] = val,
+      8 => self.prg_rom_bank = (val & 0x0f) as usize,
+      9 => {
+        self.mirroring = match val & 0b11 {
+          0 => Mirroring::Vertical,
+          1 => Mirroring::Horizontal,
+          2 => Mirroring::SingleScreenA,
+          _ => Mirroring::SingleScreenB
+        };
+      }
+      0x0a => {
+        self.irq_enabled = val & 0b1 != 0;
+        self.irq_pending = false;
+
+        if self.latches_irq {
+          self.irq_counter = self.irq_latch;
+        }
+      }
+      0x0b => self.write_irq_value(|value| (value & 0xff00) | val as u16),
+      0x0c => self.write_irq_value(|value| (value & 0xff) | (val as u16) << 8),
+      0x0d => {
+        if let Some(eeprom) = &mut self.eeprom {
+          eeprom.write(val & 0x20 != 0, val & 0x40 != 0);
+        }
+
+        // 153 reuses the bit as its PRG-RAM enable
+        self.prg_ram_enabled = val & 0x20 != 0;
+      }
+      _ => ()
+    }
+  }
+
+  fn write_irq_value(&mut self, update: impl Fn(u16) -> u16) {
+    if self.latches_irq {
+      self.irq_latch = update(self.irq_latch);
+    } else {
+      self.irq_counter = update(self.irq_counter);
+    }
+  }
+}
+
+impl MapperActions for Bandai {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff if self.chr_ram => Some(address as usize),
+      0x0000..=0x1fff => {
+        let bank = self.chr_registers[(address as usize) / CHR_BANK_SIZE] as usize % self.chr_page_size;
+
+        Some(bank * CHR_BANK_SIZE + ((address as usize) & (CHR_BANK_SIZE - 1)))
+      }
+      0x6000..=0x7fff if self.has_prg_ram && self.prg_ram_enabled => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => Some(self.translate_prg_address(address)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff if self.has_prg_ram && self.prg_ram_enabled => return Some((address - 0x6000) as usize),
+      0x6000..=0x7fff if self.registers_at_6000 => self.write_register(address, val),
+      0x8000..=0xffff if self.registers_at_8000 => self.write_register(address, val),
+      _ => ()
+    }
+
+    None
+  }
+
+  // the EEPROM's data line shows up on bit 4
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    match (address, &self.eeprom) {
+      (0x6000..=0x7fff, Some(eeprom)) => Some((eeprom.read() as u8) << 4),
+      _ => None
+    }
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    if !self.irq_enabled {
+      return;
+    }
+
+    for _ in 0..cycles {
+      if self.irq_counter == 0 {
+        self.irq_pending = true;
+      }
+
+      self.irq_counter = self.irq_counter.wrapping_sub(1);
+    }
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq_pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq_pending = val;
+  }
+
+  fn save_pending(&self) -> bool {
+    self.eeprom.as_ref().is_some_and(|eeprom| eeprom.save_pending)
+  }
+
+  fn save_data(&mut self) -> Option<Vec<u8>> {
+    let eeprom = self.eeprom.as_mut()?;
+
+    eeprom.save_pending = false;
+
+    Some(eeprom.data.clone())
+  }
+
+  fn load_save_data(&mut self, data: &[u8]) {
+    if let Some(eeprom) = &mut self.eeprom {
+      let length = data.len().min(eeprom.data.len());
+
+      eeprom.data[..length].copy_from_slice(&data[..length]);
+    }
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Bandai {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(16, None), (153, None), (157, None), (159, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Bandai::load(cartridge))
+  }
+}
@@ -0,0 +1,79 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+use super::nina001::Nina001;
+
+const PRG_ROM_BANK_SIZE: usize = 32_768;
+const CHR_RAM_SIZE: usize = 8192;
+
+// mapper 34's Nintendo half: any write to $8000-$ffff picks a 32K PRG bank, CHR is RAM
+// see https://www.nesdev.org/wiki/BNROM
+pub struct Bnrom {
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Bnrom {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    Self {
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+}
+
+impl MapperActions for Bnrom {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(address as usize),
+      0x8000..=0xffff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if let 0x8000..=0xffff = address {
+      self.prg_rom_bank = (val as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE;
+    }
+
+    None
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Bnrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(34, None), (34, Some(2))];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    // without a submapper, more than 8K of CHR-ROM means NINA-001 since BNROM can't bank it
+    if cartridge.header.submapper == 0 && cartridge.chr_rom.len() > 8192 {
+      return Box::new(Nina001::load(cartridge));
+    }
+
+    Box::new(Bnrom::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_bank() {
+    let mut mapper = test_cartridge(34, 2, 8, 0).mapper;
+
+    mapper.mem_write(0xffff, 3);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(3 * 0x8000));
+    assert_eq!(mapper.mem_read(0xffff), Some(3 * 0x8000 + 0x7fff));
+  }
+}
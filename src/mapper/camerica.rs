@@ -0,0 +1,102 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 16_384;
+const CHR_RAM_SIZE: usize = 8192;
+
+// Camerica/Codemasters' BF909x boards, UxROM-like but with the bank register at $c000-$ffff.
+// The BF9097 on Fire Hawk (submapper 1) adds single screen mirroring at $8000-$9fff.
+// Without a submapper we can't tell the boards apart, so writes to $9000-$9fff, which only
+// Fire Hawk makes, switch the mirroring too.
+// see https://www.nesdev.org/wiki/INES_Mapper_071
+pub struct Camerica {
+  fire_hawk: bool,
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Camerica {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    Self {
+      fire_hawk: cartridge.header.submapper == 1,
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+
+  fn write_mirroring(&mut self, val: u8) {
+    self.mirroring = if (val >> 4) & 0b1 == 0 {
+      Mirroring::SingleScreenA
+    } else {
+      Mirroring::SingleScreenB
+    };
+  }
+}
+
+impl MapperActions for Camerica {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(address as usize),
+      0x8000..=0xbfff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      0xc000..=0xffff => Some((self.prg_rom_page_size - 1) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1))),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x8000..=0x8fff if self.fire_hawk => self.write_mirroring(val),
+      0x9000..=0x9fff => self.write_mirroring(val),
+      0xc000..=0xffff => self.prg_rom_bank = ((val & 0x0f) as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE,
+      _ => ()
+    }
+
+    None
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Camerica {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(71, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Camerica::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cartridge::Mirroring;
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_bank() {
+    let mut mapper = test_cartridge(71, 0, 8, 0).mapper;
+
+    mapper.mem_write(0xc000, 5);
+
+    assert_eq!(mapper.mem_read(0x8001), Some(5 * 0x4000 + 1));
+    assert_eq!(mapper.mem_read(0xc001), Some(7 * 0x4000 + 1));
+  }
+
+  #[test]
+  fn fire_hawk_switches_single_screen() {
+    let mut mapper = test_cartridge(71, 1, 8, 0).mapper;
+
+    mapper.mem_write(0x8000, 0x10);
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenB);
+
+    mapper.mem_write(0x9000, 0x00);
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenA);
+  }
+}
@@ -1,19 +1,23 @@
-use crate::cartridge::Cartridge;
+use crate::cartridge::{Cartridge, Mirroring};
 
-use super::MapperActions;
+use super::{Mapper, MapperActions, MapperBoard};
 
 const CHR_BANK_SIZE: usize = 8192;
 
 pub struct Cnrom {
   chr_bank: usize,
-  chr_page_size: usize
+  chr_page_size: usize,
+  prg_rom_size: usize,
+  mirroring: Mirroring
 }
 
 impl Cnrom {
   pub fn load(cartridge: &mut Cartridge) -> Self {
     Self {
       chr_bank: 0,
-      chr_page_size:  cartridge.chr_rom.len() / CHR_BANK_SIZE
+      chr_page_size:  cartridge.chr_rom.len() / CHR_BANK_SIZE,
+      prg_rom_size: cartridge.prg_rom.len(),
+      mirroring: cartridge.mirroring
     }
   }
 }
@@ -35,7 +39,21 @@ impl MapperActions for Cnrom {
 
         Some(page | (address as usize) & (CHR_BANK_SIZE - 1))
       }
+      // 16K boards mirror PRG-ROM into both halves like NROM-128
+      0x8000..=0xffff => Some((address - 0x8000) as usize % self.prg_rom_size.max(1)),
       _ => None
     }
   }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Cnrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(3, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Cnrom::load(cartridge))
+  }
 }
\ No newline at end of file
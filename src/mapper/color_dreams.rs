@@ -0,0 +1,75 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 32_768;
+const CHR_BANK_SIZE: usize = 8192;
+
+// Color Dreams' boards, laid out like GxROM but with a 32K PRG bank in bits 0-1
+// and an 8K CHR bank in bits 4-7
+// see https://www.nesdev.org/wiki/Color_Dreams
+pub struct ColorDreams {
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  chr_bank: usize,
+  chr_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl ColorDreams {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    Self {
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_bank: 0,
+      chr_page_size: (cartridge.chr_rom.len() / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+}
+
+impl MapperActions for ColorDreams {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_bank | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x8000..=0xffff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if let 0x8000..=0xffff = address {
+      self.prg_rom_bank = ((val & 0b11) as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE;
+      self.chr_bank = ((val >> 4) as usize % self.chr_page_size) * CHR_BANK_SIZE;
+    }
+
+    None
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for ColorDreams {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(11, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(ColorDreams::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_and_chr_banks() {
+    let mut mapper = test_cartridge(11, 0, 8, 16).mapper;
+
+    mapper.mem_write(0x8000, 0xa1);
+
+    assert_eq!(mapper.mem_read(0xc000), Some(0x8000 + 0x4000));
+    assert_eq!(mapper.mem_read(0x0010), Some(10 * 0x2000 + 0x10));
+  }
+}
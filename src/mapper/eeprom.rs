@@ -0,0 +1,229 @@
+// the serial EEPROMs on Bandai's boards, driven one I2C bit at a time through a mapper register.
+// the 24C02 expects a device address byte and sends everything MSB first, while the older
+// 24C01 takes a 7 bit word address straight after the start condition, LSB first.
+// see https://www.nesdev.org/wiki/Bandai_FCG_board#Serial_EEPROM
+#[derive(Clone, Copy, PartialEq)]
+pub enum EepromChip {
+  C24C01,
+  C24C02
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EepromMode {
+  Idle,
+  DeviceAddress,
+  WordAddress,
+  Write,
+  Read,
+  // the chip pulls SDA low for a clock after every byte it receives
+  SendAck,
+  // and waits for the host to do the same after every byte it sends
+  ReceiveAck
+}
+
+pub struct Eeprom {
+  chip: EepromChip,
+  pub data: Vec<u8>,
+  mode: EepromMode,
+  next_mode: EepromMode,
+  address: u8,
+  shift: u8,
+  bits: u8,
+  ack_driven: bool,
+  acked: bool,
+  scl: bool,
+  sda: bool,
+  output: bool,
+  pub save_pending: bool
+}
+
+impl Eeprom {
+  pub fn new(chip: EepromChip) -> Self {
+    let size = match chip {
+      EepromChip::C24C01 => 128,
+      EepromChip::C24C02 => 256
+    };
+
+    Self {
+      chip,
+      data: vec![0xff; size],
+      mode: EepromMode::Idle,
+      next_mode: EepromMode::Idle,
+      address: 0,
+      shift: 0,
+      bits: 0,
+      ack_driven: false,
+      acked: false,
+      scl: false,
+      sda: false,
+      output: true,
+      save_pending: false
+    }
+  }
+
+  // what the chip is driving onto SDA
+  pub fn read(&self) -> bool {
+    self.output
+  }
+
+  pub fn write(&mut self, scl: bool, sda: bool) {
+    if scl && self.scl {
+      // SDA changing while the clock is high marks a start or stop
+      if self.sda && !sda {
+        self.start();
+      } else if !self.sda && sda {
+        self.mode = EepromMode::Idle;
+        self.output = true;
+      }
+    } else if scl && !self.scl {
+      self.clock_rise(sda);
+    } else if !scl && self.scl {
+      self.clock_fall();
+    }
+
+    self.scl = scl;
+    self.sda = sda;
+  }
+
+  fn start(&mut self) {
+    self.mode = match self.chip {
+      EepromChip::C24C01 => EepromMode::WordAddress,
+      EepromChip::C24C02 => EepromMode::DeviceAddress
+    };
+
+    self.shift = 0;
+    self.bits = 0;
+    self.output = true;
+  }
+
+  fn address_mask(&self) -> u8 {
+    (self.data.len() - 1) as u8
+  }
+
+  // writes wrap inside a page, 4 bytes on the 24C01 and 8 on the 24C02
+  fn next_write_address(&self) -> u8 {
+    let page_mask = match self.chip {
+      EepromChip::C24C01 => 0b11,
+      EepromChip::C24C02 => 0b111
+    };
+
+    (self.address & !page_mask) | (self.address.wrapping_add(1) & page_mask)
+  }
+
+  fn shift_in(&mut self, bit: bool) {
+    match self.chip {
+      EepromChip::C24C01 => self.shift |= (bit as u8) << self.bits,
+      EepromChip::C24C02 => self.shift = (self.shift << 1) | bit as u8
+    }
+
+    self.bits += 1;
+  }
+
+  fn output_bit(&self) -> bool {
+    let byte = self.data[self.address as usize];
+
+    let bit = match self.chip {
+      EepromChip::C24C01 => self.bits,
+      EepromChip::C24C02 => 7 - self.bits
+    };
+
+    (byte >> bit) & 0b1 != 0
+  }
+
+  fn acknowledge(&mut self, next_mode: EepromMode) {
+    self.mode = EepromMode::SendAck;
+    self.next_mode = next_mode;
+    self.ack_driven = false;
+  }
+
+  fn receive_byte(&mut self) {
+    let byte = self.shift;
+
+    match self.mode {
+      EepromMode::DeviceAddress => {
+        if byte & 0xf0 != 0xa0 {
+          self.mode = EepromMode::Idle;
+        } else if byte & 0b1 != 0 {
+          self.acknowledge(EepromMode::Read);
+        } else {
+          self.acknowledge(EepromMode::WordAddress);
+        }
+      }
+      EepromMode::WordAddress => match self.chip {
+        EepromChip::C24C01 => {
+          // the 8th bit is read/write
+          self.address = byte & 0x7f;
+          self.acknowledge(if byte & 0x80 != 0 { EepromMode::Read } else { EepromMode::Write });
+        }
+        EepromChip::C24C02 => {
+          self.address = byte;
+          self.acknowledge(EepromMode::Write);
+        }
+      },
+      EepromMode::Write => {
+        self.data[self.address as usize] = byte;
+        self.address = self.next_write_address();
+        self.save_pending = true;
+
+        self.acknowledge(EepromMode::Write);
+      }
+      _ => ()
+    }
+  }
+
+  fn clock_rise(&mut self, sda: bool) {
+    match self.mode {
+      EepromMode::DeviceAddress | EepromMode::WordAddress | EepromMode::Write => {
+        self.shift_in(sda);
+
+        if self.bits == 8 {
+          self.receive_byte();
+        }
+      }
+      EepromMode::Read => {
+        self.bits += 1;
+
+        if self.bits == 8 {
+          self.mode = EepromMode::ReceiveAck;
+          self.acked = false;
+          self.ack_driven = false;
+        }
+      }
+      EepromMode::ReceiveAck => {
+        // a low SDA asks for the next byte, high ends the read
+        self.acked = !sda;
+        self.ack_driven = true;
+
+        if self.acked {
+          self.address = self.address.wrapping_add(1) & self.address_mask();
+        } else {
+          self.mode = EepromMode::Idle;
+        }
+      }
+      _ => ()
+    }
+  }
+
+  // the chip only changes SDA while the clock is low
+  fn clock_fall(&mut self) {
+    match self.mode {
+      EepromMode::SendAck if !self.ack_driven => {
+        self.output = false;
+        self.ack_driven = true;
+      }
+      EepromMode::SendAck => self.begin_byte(self.next_mode),
+      EepromMode::Read => self.output = self.output_bit(),
+      EepromMode::ReceiveAck if self.ack_driven && self.acked => self.begin_byte(EepromMode::Read),
+      EepromMode::ReceiveAck => self.output = true,
+      _ => ()
+    }
+  }
+
+  fn begin_byte(&mut self, mode: EepromMode) {
+    self.mode = mode;
+    self.shift = 0;
+    self.bits = 0;
+
+    self.output = if mode == EepromMode::Read { self.output_bit() } else { true };
+  }
+}
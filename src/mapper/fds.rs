@@ -0,0 +1,473 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::cartridge::fds::{self, DISK_SIDE_SIZE};
+use crate::patch::{self, ips};
+
+use super::MapperActions;
+
+const PRG_RAM_SIZE: usize = 32_768;
+const CHR_RAM_SIZE: usize = 8192;
+
+// roughly how long the drive takes to spin up and reach the first block
+const SPIN_UP_DELAY: u32 = 50_000;
+// a byte passes under the head about every 150 CPU cycles (96.4 kbit/s)
+const BYTE_TRANSFER_DELAY: u32 = 150;
+// games poll $4032 to notice a disk change, so keep reporting "no disk" for a while after inserting
+const INSERT_DELAY: u32 = 600_000;
+
+// see https://www.nesdev.org/wiki/Family_Computer_Disk_System
+pub struct Fds {
+  prg_ram: Vec<u8>,
+  original_image: Vec<u8>,
+  disk_sides: Vec<Vec<u8>>,
+  current_side: Option<usize>,
+  insert_delay: u32,
+  mirroring: Mirroring,
+  registers: FdsRegisters,
+  drive: DiskDrive,
+  disk_modified: bool,
+  irq_pending: bool
+}
+
+struct FdsRegisters {
+  irq_reload: u16,
+  irq_counter: u16,
+  irq_repeat: bool,
+  irq_enabled: bool,
+  disk_registers_enabled: bool,
+  sound_registers_enabled: bool,
+  write_data: u8,
+  read_data: u8,
+  external_output: u8
+}
+
+struct DiskDrive {
+  motor_on: bool,
+  reset_transfer: bool,
+  read_mode: bool,
+  crc_control: bool,
+  previous_crc_control: bool,
+  disk_ready: bool,
+  disk_irq_enabled: bool,
+  scanning: bool,
+  end_of_head: bool,
+  gap_ended: bool,
+  position: usize,
+  delay: u32,
+  crc: u16,
+  timer_irq: bool,
+  transfer_complete: bool
+}
+
+impl Fds {
+  pub fn load(cartridge: &mut Cartridge, image: &[u8]) -> Self {
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    let mut fds = Self {
+      prg_ram: vec![0; PRG_RAM_SIZE],
+      original_image: fds::strip_header(image).to_vec(),
+      disk_sides: Vec::new(),
+      current_side: None,
+      insert_delay: 0,
+      mirroring: Mirroring::Horizontal,
+      registers: FdsRegisters {
+        irq_reload: 0,
+        irq_counter: 0,
+        irq_repeat: false,
+        irq_enabled: false,
+        disk_registers_enabled: false,
+        sound_registers_enabled: false,
+        write_data: 0,
+        read_data: 0,
+        external_output: 0
+      },
+      drive: DiskDrive {
+        motor_on: false,
+        reset_transfer: false,
+        read_mode: true,
+        crc_control: false,
+        previous_crc_control: false,
+        disk_ready: false,
+        disk_irq_enabled: false,
+        scanning: false,
+        end_of_head: true,
+        gap_ended: false,
+        position: 0,
+        delay: 0,
+        crc: 0,
+        timer_irq: false,
+        transfer_complete: false
+      },
+      disk_modified: false,
+      irq_pending: false
+    };
+
+    fds.load_sides(image);
+
+    if !fds.disk_sides.is_empty() {
+      fds.current_side = Some(0);
+    }
+
+    fds
+  }
+
+  fn load_sides(&mut self, image: &[u8]) {
+    self.disk_sides = fds::parse(image)
+      .unwrap_or_default()
+      .iter()
+      .map(|side| fds::add_gaps(side))
+      .collect();
+  }
+
+  fn disk_inserted(&self) -> bool {
+    self.current_side.is_some() && self.insert_delay == 0
+  }
+
+  // rebuilds the whole image in .fds layout so it can be diffed against the original
+  fn modified_image(&self) -> Vec<u8> {
+    let mut image: Vec<u8> = Vec::with_capacity(self.disk_sides.len() * DISK_SIDE_SIZE);
+
+    for side in &self.disk_sides {
+      image.extend_from_slice(&fds::strip_gaps(side));
+    }
+
+    image
+  }
+
+  fn clock_timer(&mut self) {
+    if self.registers.irq_enabled {
+      if self.registers.irq_counter == 0 {
+        self.drive.timer_irq = true;
+        self.irq_pending = true;
+        self.registers.irq_counter = self.registers.irq_reload;
+
+        if !self.registers.irq_repeat {
+          self.registers.irq_enabled = false;
+        }
+      } else {
+        self.registers.irq_counter -= 1;
+      }
+    }
+  }
+
+  fn clock_drive(&mut self) {
+    if self.insert_delay > 0 {
+      self.insert_delay -= 1;
+      return;
+    }
+
+    let side = match self.current_side {
+      Some(side) if self.drive.motor_on => side,
+      _ => {
+        self.drive.end_of_head = true;
+        self.drive.scanning = false;
+        return;
+      }
+    };
+
+    if self.drive.reset_transfer && !self.drive.scanning {
+      return;
+    }
+
+    if self.drive.end_of_head {
+      self.drive.delay = SPIN_UP_DELAY;
+      self.drive.end_of_head = false;
+      self.drive.position = 0;
+      self.drive.gap_ended = false;
+      return;
+    }
+
+    if self.drive.delay > 0 {
+      self.drive.delay -= 1;
+      return;
+    }
+
+    self.drive.scanning = true;
+
+    let need_irq = self.drive.disk_irq_enabled;
+
+    if self.drive.read_mode {
+      let data = self.disk_sides[side][self.drive.position];
+
+      if !self.drive.previous_crc_control {
+        self.update_crc(data);
+      }
+
+      let mut need_irq = need_irq;
+
+      if !self.drive.disk_ready {
+        self.drive.gap_ended = false;
+        self.drive.crc = 0;
+      } else if data != 0 && !self.drive.gap_ended {
+        // the start mark at the end of a gap is latched without raising an IRQ
+        self.drive.gap_ended = true;
+        need_irq = false;
+      }
+
+      if self.drive.gap_ended {
+        self.drive.transfer_complete = true;
+        self.registers.read_data = data;
+
+        if need_irq {
+          self.irq_pending = true;
+        }
+      }
+    } else {
+      if !self.drive.crc_control {
+        self.drive.transfer_complete = true;
+
+        if need_irq {
+          self.irq_pending = true;
+        }
+      }
+
+      let mut data = if self.drive.previous_crc_control { 0 } else { self.registers.write_data };
+
+      if !self.drive.disk_ready {
+        data = 0;
+      }
+
+      if !self.drive.crc_control {
+        self.update_crc(data);
+      } else {
+        if !self.drive.previous_crc_control {
+          self.update_crc(0);
+          self.update_crc(0);
+        }
+
+        data = self.drive.crc as u8;
+        self.drive.crc >>= 8;
+      }
+
+      self.write_disk(side, data);
+      self.drive.gap_ended = false;
+    }
+
+    self.drive.previous_crc_control = self.drive.crc_control;
+    self.drive.position += 1;
+
+    if self.drive.position >= self.disk_sides[side].len() {
+      self.drive.motor_on = false;
+    } else {
+      self.drive.delay = BYTE_TRANSFER_DELAY;
+    }
+  }
+
+  fn write_disk(&mut self, side: usize, data: u8) {
+    // the write head trails the read head by a couple of bytes
+    if self.drive.position >= 2 {
+      let position = self.drive.position - 2;
+
+      if self.disk_sides[side][position] != data {
+        self.disk_sides[side][position] = data;
+        self.disk_modified = true;
+      }
+    }
+  }
+
+  // CRC-16/KERMIT, as used by the drive's block checksums
+  fn update_crc(&mut self, data: u8) {
+    let mut value = data as u16;
+
+    for _ in 0..8 {
+      let carry = (self.drive.crc & 0b1) ^ (value & 0b1);
+
+      self.drive.crc >>= 1;
+      value >>= 1;
+
+      if carry == 1 {
+        self.drive.crc ^= 0x8408;
+      }
+    }
+  }
+
+  fn read_register(&mut self, address: u16) -> Option<u8> {
+    if !self.registers.disk_registers_enabled {
+      return None;
+    }
+
+    match address {
+      0x4030 => {
+        let mut status = 0;
+
+        if self.drive.timer_irq {
+          status |= 0b1;
+        }
+        if self.drive.transfer_complete {
+          status |= 0b10;
+        }
+
+        self.drive.timer_irq = false;
+        self.drive.transfer_complete = false;
+        self.irq_pending = false;
+
+        Some(status)
+      }
+      0x4031 => {
+        self.drive.transfer_complete = false;
+        self.irq_pending = false;
+
+        Some(self.registers.read_data)
+      }
+      0x4032 => {
+        // bit 6 is open bus, which is normally $40 since the register is read via $4032
+        let mut status = 0b1000000;
+
+        if !self.disk_inserted() {
+          status |= 0b101;
+        }
+        if !self.disk_inserted() || !self.drive.scanning {
+          status |= 0b10;
+        }
+
+        Some(status)
+      }
+      // bit 7 reports a good battery in the RAM adapter
+      0x4033 => Some(0b10000000),
+      _ => None
+    }
+  }
+
+  fn write_register(&mut self, address: u16, val: u8) {
+    match address {
+      0x4020 => self.registers.irq_reload = (self.registers.irq_reload & 0xff00) | val as u16,
+      0x4021 => self.registers.irq_reload = (self.registers.irq_reload & 0xff) | (val as u16) << 8,
+      0x4022 => {
+        self.registers.irq_repeat = val & 0b1 == 1;
+        self.registers.irq_enabled = (val >> 1) & 0b1 == 1 && self.registers.disk_registers_enabled;
+
+        if self.registers.irq_enabled {
+          self.registers.irq_counter = self.registers.irq_reload;
+        } else {
+          self.drive.timer_irq = false;
+          self.irq_pending = false;
+        }
+      }
+      0x4023 => {
+        self.registers.disk_registers_enabled = val & 0b1 == 1;
+        self.registers.sound_registers_enabled = (val >> 1) & 0b1 == 1;
+
+        if !self.registers.disk_registers_enabled {
+          self.registers.irq_enabled = false;
+          self.drive.timer_irq = false;
+          self.irq_pending = false;
+        }
+      }
+      0x4024 if self.registers.disk_registers_enabled => {
+        self.registers.write_data = val;
+        self.drive.transfer_complete = false;
+        self.irq_pending = false;
+      }
+      0x4025 if self.registers.disk_registers_enabled => {
+        self.irq_pending = false;
+
+        self.drive.motor_on = val & 0b1 == 1;
+        self.drive.reset_transfer = (val >> 1) & 0b1 == 1;
+        self.drive.read_mode = (val >> 2) & 0b1 == 1;
+        self.mirroring = if (val >> 3) & 0b1 == 1 { Mirroring::Horizontal } else { Mirroring::Vertical };
+        self.drive.crc_control = (val >> 4) & 0b1 == 1;
+        self.drive.disk_ready = (val >> 6) & 0b1 == 1;
+        self.drive.disk_irq_enabled = (val >> 7) & 0b1 == 1;
+      }
+      0x4026 if self.registers.disk_registers_enabled => self.registers.external_output = val,
+      _ => ()
+    }
+  }
+}
+
+impl MapperActions for Fds {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(address as usize),
+      // the BIOS lives in the cartridge's PRG-ROM
+      0xe000..=0xffff => Some((address - 0xe000) as usize),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, _val: u8) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(address as usize),
+      _ => None
+    }
+  }
+
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    match address {
+      0x4020..=0x403f => self.read_register(address),
+      0x6000..=0xdfff => Some(self.prg_ram[(address - 0x6000) as usize]),
+      _ => None
+    }
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    match address {
+      0x4020..=0x403f => {
+        self.write_register(address, val);
+        true
+      }
+      0x6000..=0xdfff => {
+        self.prg_ram[(address - 0x6000) as usize] = val;
+        true
+      }
+      _ => false
+    }
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    for _ in 0..cycles {
+      self.clock_timer();
+      self.clock_drive();
+    }
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq_pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq_pending = val;
+  }
+
+  fn disk_sides(&self) -> usize {
+    self.disk_sides.len()
+  }
+
+  fn current_disk_side(&self) -> Option<usize> {
+    self.current_side
+  }
+
+  fn insert_disk(&mut self, side: usize) {
+    if side < self.disk_sides.len() {
+      self.current_side = Some(side);
+      self.insert_delay = INSERT_DELAY;
+    }
+  }
+
+  fn eject_disk(&mut self) {
+    self.current_side = None;
+  }
+
+  fn save_pending(&self) -> bool {
+    self.disk_modified
+  }
+
+  // disk writes are saved as an IPS patch against the original image so the
+  // image itself is never touched
+  fn save_data(&mut self) -> Option<Vec<u8>> {
+    self.disk_modified = false;
+
+    Some(ips::create(&self.original_image, &self.modified_image()))
+  }
+
+  fn load_save_data(&mut self, data: &[u8]) {
+    if let Ok(image) = patch::apply(&self.original_image, data) {
+      self.load_sides(&image);
+    }
+  }
+}
@@ -0,0 +1,193 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::cpu::apu::FULL_VOLUME_PULSE;
+
+use super::{Mapper, MapperActions, MapperBoard};
+use super::sunsoft5b::Sunsoft5b;
+
+const PRG_ROM_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+
+// Sunsoft5b::output already scales each channel's logarithmic volume to 0.0-1.0
+const OUTPUT_LEVEL: f32 = FULL_VOLUME_PULSE;
+
+// what command 8 maps into $6000-$7fff
+#[derive(Clone, Copy, PartialEq)]
+enum LowPrgBank {
+  Rom(usize),
+  Ram,
+  Disabled
+}
+
+// the FME-7 and its 5B variant, which adds the audio chip. Both sit behind a command
+// register at $8000 and a parameter register at $a000.
+// see https://www.nesdev.org/wiki/Sunsoft_FME-7
+pub struct Fme7 {
+  command: u8,
+  low_prg_bank: LowPrgBank,
+  prg_rom_banks: [usize; 3],
+  prg_page_size: usize,
+  // ROM can be banked into $6000, where the CPU only looks at PRG-RAM
+  prg_rom: Vec<u8>,
+  chr_banks: [usize; 8],
+  chr_page_size: usize,
+  mirroring: Mirroring,
+  irq_enabled: bool,
+  irq_counter_enabled: bool,
+  irq_counter: u16,
+  irq_pending: bool,
+  audio: Sunsoft5b
+}
+
+impl Fme7 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    Self {
+      command: 0,
+      low_prg_bank: LowPrgBank::Rom(0),
+      prg_rom_banks: [0; 3],
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      prg_rom: cartridge.prg_rom.clone(),
+      chr_banks: [0; 8],
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring,
+      irq_enabled: false,
+      irq_counter_enabled: false,
+      irq_counter: 0,
+      irq_pending: false,
+      audio: Sunsoft5b::default()
+    }
+  }
+
+  fn prg_bank_address(&self, val: u8) -> usize {
+    ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE
+  }
+
+  fn write_parameter(&mut self, val: u8) {
+    match self.command {
+      0..=7 => self.chr_banks[self.command as usize] = (val as usize % self.chr_page_size) * CHR_BANK_SIZE,
+      8 => {
+        self.low_prg_bank = match (val & 0x40 != 0, val & 0x80 != 0) {
+          (false, _) => LowPrgBank::Rom(self.prg_bank_address(val)),
+          (true, true) => LowPrgBank::Ram,
+          (true, false) => LowPrgBank::Disabled
+        };
+      }
+      9..=11 => self.prg_rom_banks[(self.command - 9) as usize] = self.prg_bank_address(val),
+      12 => {
+        self.mirroring = match val & 0b11 {
+          0 => Mirroring::Vertical,
+          1 => Mirroring::Horizontal,
+          2 => Mirroring::SingleScreenA,
+          _ => Mirroring::SingleScreenB
+        };
+      }
+      13 => {
+        self.irq_enabled = val & 0b1 != 0;
+        self.irq_counter_enabled = val & 0x80 != 0;
+        self.irq_pending = false;
+      }
+      14 => self.irq_counter = (self.irq_counter & 0xff00) | val as u16,
+      _ => self.irq_counter = (self.irq_counter & 0xff) | (val as u16) << 8
+    }
+  }
+}
+
+impl MapperActions for Fme7 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_banks[(address as usize) / CHR_BANK_SIZE] | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x6000..=0x7fff if self.low_prg_bank == LowPrgBank::Ram => Some((address - 0x6000) as usize),
+      0x8000..=0xdfff => Some(self.prg_rom_banks[((address - 0x8000) as usize) / PRG_ROM_BANK_SIZE] | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      0xe000..=0xffff => Some((self.prg_page_size - 1) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1))),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff if self.low_prg_bank == LowPrgBank::Ram => return Some((address - 0x6000) as usize),
+      0x8000..=0x9fff => self.command = val & 0x0f,
+      0xa000..=0xbfff => self.write_parameter(val),
+      0xc000..=0xdfff => self.audio.select_register(val),
+      0xe000..=0xffff => self.audio.write_data(val),
+      _ => ()
+    }
+
+    None
+  }
+
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    match (address, self.low_prg_bank) {
+      (0x6000..=0x7fff, LowPrgBank::Rom(bank)) => self.prg_rom.get(bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)).copied(),
+      _ => None
+    }
+  }
+
+  // the counter runs every CPU cycle, but only raises an IRQ when it wraps with IRQs enabled
+  fn tick(&mut self, cycles: u16) {
+    if self.irq_counter_enabled {
+      for _ in 0..cycles {
+        self.irq_counter = self.irq_counter.wrapping_sub(1);
+
+        if self.irq_counter == 0xffff && self.irq_enabled {
+          self.irq_pending = true;
+        }
+      }
+    }
+
+    self.audio.tick(cycles);
+  }
+
+  fn audio_output(&self) -> f32 {
+    self.audio.output() * OUTPUT_LEVEL
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq_pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq_pending = val;
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Fme7 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(69, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Fme7::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn counts_every_cycle_of_a_long_tick() {
+    let mut mapper = test_cartridge(69, 0, 8, 16).mapper;
+
+    // a counter of 513 underflows on the 514th cycle, which an OAM DMA covers in one tick
+    for (command, val) in [(14, 0x01), (15, 0x02), (13, 0x81)] {
+      mapper.mem_write(0x8000, command);
+      mapper.mem_write(0xa000, val);
+    }
+
+    mapper.tick(513);
+    assert!(!mapper.irq_pending());
+
+    mapper.tick(1);
+    assert!(mapper.irq_pending());
+  }
+}
@@ -0,0 +1,74 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 32_768;
+const CHR_BANK_SIZE: usize = 8192;
+
+// GNROM and MHROM: one latch picking a 32K PRG bank with bits 4-5 and an 8K CHR bank with bits 0-1
+// see https://www.nesdev.org/wiki/GxROM
+pub struct Gxrom {
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  chr_bank: usize,
+  chr_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Gxrom {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    Self {
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_bank: 0,
+      chr_page_size: (cartridge.chr_rom.len() / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+}
+
+impl MapperActions for Gxrom {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_bank | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x8000..=0xffff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if let 0x8000..=0xffff = address {
+      self.prg_rom_bank = (((val >> 4) & 0b11) as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE;
+      self.chr_bank = ((val & 0b11) as usize % self.chr_page_size) * CHR_BANK_SIZE;
+    }
+
+    None
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Gxrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(66, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Gxrom::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_and_chr_banks() {
+    let mut mapper = test_cartridge(66, 0, 8, 4).mapper;
+
+    mapper.mem_write(0x8000, 0b0010_0011);
+
+    assert_eq!(mapper.mem_read(0x8123), Some(2 * 0x8000 + 0x123));
+    assert_eq!(mapper.mem_read(0x1456), Some(3 * 0x2000 + 0x1456));
+  }
+}
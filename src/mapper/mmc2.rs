@@ -0,0 +1,148 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard, PpuFetch};
+
+const PRG_RAM_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 4096;
+
+const MMC2_PRG_ROM_BANK_SIZE: usize = 8192;
+const MMC4_PRG_ROM_BANK_SIZE: usize = 16_384;
+
+// each half of the pattern table has a latch that flips between its $fd and $fe
+// banks whenever the PPU fetches one of those two tiles
+#[derive(Clone, Copy)]
+enum Latch {
+  Fd,
+  Fe
+}
+
+// MMC2 (mapper 9) and MMC4 (mapper 10) only differ in PRG banking and how much
+// of the tile the left latch watches.
+// see https://www.nesdev.org/wiki/MMC2 and https://www.nesdev.org/wiki/MMC4
+pub struct Mmc2 {
+  is_mmc4: bool,
+  prg_rom_bank: usize,
+  prg_rom_bank_size: usize,
+  prg_rom_page_size: usize,
+  prg_rom_len: usize,
+  chr_registers: [[u8; 2]; 2],
+  latches: [Latch; 2],
+  chr_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Mmc2 {
+  pub fn load(cartridge: &mut Cartridge, is_mmc4: bool) -> Self {
+    if is_mmc4 && cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    let prg_rom_bank_size = if is_mmc4 { MMC4_PRG_ROM_BANK_SIZE } else { MMC2_PRG_ROM_BANK_SIZE };
+
+    Self {
+      is_mmc4,
+      prg_rom_bank: 0,
+      prg_rom_bank_size,
+      prg_rom_page_size: (cartridge.prg_rom.len() / prg_rom_bank_size).max(1),
+      prg_rom_len: cartridge.prg_rom.len(),
+      chr_registers: [[0; 2]; 2],
+      latches: [Latch::Fe; 2],
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+
+  fn translate_prg_address(&self, address: u16) -> usize {
+    let offset = (address - 0x8000) as usize;
+
+    if offset < self.prg_rom_bank_size {
+      self.prg_rom_bank | offset
+    } else {
+      // everything past the switchable bank is fixed to the end of PRG-ROM
+      let fixed_len = 0x8000 - self.prg_rom_bank_size;
+
+      self.prg_rom_len.saturating_sub(fixed_len) + (offset - self.prg_rom_bank_size)
+    }
+  }
+
+  fn translate_chr_address(&self, address: u16) -> usize {
+    let half = (address as usize) / CHR_BANK_SIZE;
+    let bank = self.chr_registers[half][self.latches[half] as usize] as usize;
+
+    (bank % self.chr_page_size) * CHR_BANK_SIZE + ((address as usize) & (CHR_BANK_SIZE - 1))
+  }
+
+  // the latch flips after the fetch, so the tile that triggers it still comes from the old bank
+  fn update_latch(&mut self, address: u16) {
+    let half = (address as usize) / CHR_BANK_SIZE;
+    let tile_address = address & 0x0fff;
+
+    // the MMC2's left latch only reacts to the first row of the tile, everything else to the whole tile
+    let watches_whole_tile = half == 1 || self.is_mmc4;
+
+    match tile_address {
+      0x0fd8 => self.latches[half] = Latch::Fd,
+      0x0fe8 => self.latches[half] = Latch::Fe,
+      0x0fd9..=0x0fdf if watches_whole_tile => self.latches[half] = Latch::Fd,
+      0x0fe9..=0x0fef if watches_whole_tile => self.latches[half] = Latch::Fe,
+      _ => ()
+    }
+  }
+}
+
+impl MapperActions for Mmc2 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.translate_chr_address(address)),
+      0x6000..=0x7fff if self.is_mmc4 => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => Some(self.translate_prg_address(address)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff if self.is_mmc4 => return Some((address - 0x6000) as usize),
+      0xa000..=0xafff => {
+        self.prg_rom_bank = ((val & 0b1111) as usize % self.prg_rom_page_size) * self.prg_rom_bank_size;
+      }
+      0xb000..=0xefff => {
+        let register = ((address - 0xb000) / 0x1000) as usize;
+
+        self.chr_registers[register / 2][register % 2] = val & 0b11111;
+      }
+      0xf000..=0xffff => {
+        self.mirroring = if val & 0b1 == 0 {
+          Mirroring::Vertical
+        } else {
+          Mirroring::Horizontal
+        };
+      }
+      _ => ()
+    }
+
+    None
+  }
+
+  fn read_chr(&mut self, address: u16, _fetch: PpuFetch) -> Option<usize> {
+    let mapped_address = self.translate_chr_address(address);
+
+    self.update_latch(address);
+
+    Some(mapped_address)
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Mmc2 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(9, None), (10, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Mmc2::load(cartridge, cartridge.header.mapper == 10))
+  }
+}
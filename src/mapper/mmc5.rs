@@ -0,0 +1,651 @@
+use crate::cartridge::Cartridge;
+use crate::cpu::apu::pulse::{Pulse, PulseChannel};
+
+use super::{Mapper, MapperActions, MapperBoard, Nametable, PpuFetch};
+
+const PRG_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+const EXRAM_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+
+// the pulses' envelopes and length counters run off a fixed 240 Hz timer instead of the APU frame counter
+const AUDIO_FRAME_CYCLES: u16 = 7457;
+
+// nametable pages selected by $5105
+const CIRAM_A: u8 = 0;
+const CIRAM_B: u8 = 1;
+const EXRAM_NAMETABLE: u8 = 2;
+const FILL_NAMETABLE: u8 = 3;
+
+// ExRAM modes selected by $5104
+const EXRAM_MODE_NAMETABLE: u8 = 0;
+const EXRAM_MODE_EXTENDED_ATTRIBUTES: u8 = 1;
+const EXRAM_MODE_READ_WRITE: u8 = 2;
+
+// real hardware gives up after 3 idle CPU cycles, but the PPU here doesn't touch the bus
+// during hblank, so wait out a whole scanline instead
+const IDLE_CYCLES_OUT_OF_FRAME: u16 = 114;
+
+// tiles per scanline the MMC5 counts, including the two fetched early at the end of the previous line
+const TILES_PER_SCANLINE: u8 = 34;
+
+#[derive(Clone, Copy)]
+enum PrgBank {
+  Rom(usize),
+  Ram(usize)
+}
+
+// see https://www.nesdev.org/wiki/MMC5
+pub struct Mmc5 {
+  prg_ram: Vec<u8>,
+  exram: Vec<u8>,
+  prg_banks: [PrgBank; 4],
+  prg_mode: u8,
+  prg_registers: [u8; 5],
+  prg_rom_page_size: usize,
+  prg_ram_page_size: usize,
+  prg_ram_protect: [u8; 2],
+  chr_mode: u8,
+  chr_registers: [u16; 12],
+  chr_upper_bits: u16,
+  last_chr_set_background: bool,
+  chr_page_size: usize,
+  tall_sprites: bool,
+  exram_mode: u8,
+  nametable_mapping: u8,
+  fill_tile: u8,
+  fill_attribute: u8,
+  split_control: u8,
+  split_scroll: u8,
+  split_bank: u8,
+  irq_compare: u8,
+  irq_enabled: bool,
+  irq_status: bool,
+  irq_line: bool,
+  in_frame: bool,
+  scanline: u8,
+  last_ppu_address: u16,
+  repeated_reads: u8,
+  tile_fetches: u8,
+  ppu_reading: bool,
+  idle_cycles: u16,
+  extended_attribute: u8,
+  split_tile: Option<u8>,
+  multiplicand: u8,
+  multiplier: u8,
+  pulse1: Pulse,
+  pulse2: Pulse,
+  pcm_read_mode: bool,
+  pcm_output: u8,
+  audio_status: u8,
+  audio_half_cycle: bool,
+  audio_frame_cycles: u16,
+  save_pending: bool
+}
+
+impl Mmc5 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    let mut prg_ram = std::mem::take(&mut cartridge.prg_ram);
+
+    if prg_ram.len() < PRG_RAM_SIZE {
+      prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_len = if cartridge.chr_rom.is_empty() {
+      cartridge.chr_ram.len()
+    } else {
+      cartridge.chr_rom.len()
+    };
+
+    let mut mmc5 = Self {
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_BANK_SIZE).max(1),
+      prg_ram_page_size: (prg_ram.len() / PRG_BANK_SIZE).max(1),
+      prg_ram,
+      exram: vec![0; EXRAM_SIZE],
+      prg_banks: [PrgBank::Rom(0); 4],
+      prg_mode: 3,
+      prg_registers: [0, 0, 0, 0, 0xff],
+      prg_ram_protect: [0; 2],
+      chr_mode: 0,
+      chr_registers: [0; 12],
+      chr_upper_bits: 0,
+      last_chr_set_background: false,
+      chr_page_size: (chr_len / CHR_BANK_SIZE).max(1),
+      tall_sprites: false,
+      exram_mode: 0,
+      nametable_mapping: 0,
+      fill_tile: 0,
+      fill_attribute: 0,
+      split_control: 0,
+      split_scroll: 0,
+      split_bank: 0,
+      irq_compare: 0,
+      irq_enabled: false,
+      irq_status: false,
+      irq_line: false,
+      in_frame: false,
+      scanline: 0,
+      last_ppu_address: 0,
+      repeated_reads: 0,
+      tile_fetches: 0,
+      ppu_reading: false,
+      idle_cycles: 0,
+      extended_attribute: 0,
+      split_tile: None,
+      multiplicand: 0xff,
+      multiplier: 0xff,
+      pulse1: Pulse::new(PulseChannel::Two),
+      pulse2: Pulse::new(PulseChannel::Two),
+      pcm_read_mode: false,
+      pcm_output: 0,
+      audio_status: 0,
+      audio_half_cycle: false,
+      audio_frame_cycles: 0,
+      save_pending: false
+    };
+
+    mmc5.update_prg_banks();
+
+    mmc5
+  }
+
+  fn rom_bank(&self, bank: usize) -> PrgBank {
+    PrgBank::Rom((bank % self.prg_rom_page_size) * PRG_BANK_SIZE)
+  }
+
+  // bit 7 of $5114-$5116 picks ROM, otherwise the bank comes from PRG-RAM
+  fn prg_bank(&self, register: u8, mask: u8, offset: u8) -> PrgBank {
+    let bank = ((register & 0x7f & mask) | offset) as usize;
+
+    if register & 0x80 != 0 {
+      self.rom_bank(bank)
+    } else {
+      PrgBank::Ram((bank % self.prg_ram_page_size) * PRG_BANK_SIZE)
+    }
+  }
+
+  fn update_prg_banks(&mut self) {
+    let registers = self.prg_registers;
+    // $5117 is always ROM
+    let last = registers[4] | 0x80;
+
+    self.prg_banks = match self.prg_mode {
+      0 => [
+        self.prg_bank(last, 0xfc, 0),
+        self.prg_bank(last, 0xfc, 1),
+        self.prg_bank(last, 0xfc, 2),
+        self.prg_bank(last, 0xfc, 3)
+      ],
+      1 => [
+        self.prg_bank(registers[2], 0xfe, 0),
+        self.prg_bank(registers[2], 0xfe, 1),
+        self.prg_bank(last, 0xfe, 0),
+        self.prg_bank(last, 0xfe, 1)
+      ],
+      2 => [
+        self.prg_bank(registers[2], 0xfe, 0),
+        self.prg_bank(registers[2], 0xfe, 1),
+        self.prg_bank(registers[3], 0xff, 0),
+        self.prg_bank(last, 0xff, 0)
+      ],
+      _ => [
+        self.prg_bank(registers[1], 0xff, 0),
+        self.prg_bank(registers[2], 0xff, 0),
+        self.prg_bank(registers[3], 0xff, 0),
+        self.prg_bank(last, 0xff, 0)
+      ]
+    };
+  }
+
+  fn chr_bank_address(&self, bank: usize) -> usize {
+    (bank % self.chr_page_size) * CHR_BANK_SIZE
+  }
+
+  // $5120-$5127 cover the whole pattern table, $5128-$512b only the first half which is mirrored
+  fn chr_bank(&self, index: usize, background_set: bool) -> usize {
+    let registers = &self.chr_registers;
+
+    let bank = if background_set {
+      match self.chr_mode {
+        0 => registers[11] as usize * 8 + (index & 3),
+        1 => registers[11] as usize * 4 + (index & 3),
+        2 => registers[8 + ((index & 3) / 2) * 2 + 1] as usize * 2 + (index & 1),
+        _ => registers[8 + (index & 3)] as usize
+      }
+    } else {
+      match self.chr_mode {
+        0 => registers[7] as usize * 8 + index,
+        1 => registers[(index / 4) * 4 + 3] as usize * 4 + (index & 3),
+        2 => registers[(index / 2) * 2 + 1] as usize * 2 + (index & 1),
+        _ => registers[index] as usize
+      }
+    };
+
+    self.chr_bank_address(bank)
+  }
+
+  fn prg_ram_writable(&self) -> bool {
+    self.prg_ram_protect[0] & 0b11 == 0b10 && self.prg_ram_protect[1] & 0b11 == 0b01
+  }
+
+  fn write_prg_ram(&mut self, offset: usize, val: u8) {
+    if self.prg_ram_writable() {
+      if let Some(byte) = self.prg_ram.get_mut(offset) {
+        *byte = val;
+        self.save_pending = true;
+      }
+    }
+  }
+
+  fn nametable_page(&self, address: u16) -> u8 {
+    let quadrant = ((address - 0x2000) / 0x400) & 0b11;
+
+    (self.nametable_mapping >> (quadrant * 2)) & 0b11
+  }
+
+  // three reads in a row from the same nametable address only happen at the end of
+  // a scanline, which is how the MMC5 keeps track of where the PPU is
+  fn watch_ppu_read(&mut self, address: u16) {
+    self.ppu_reading = true;
+
+    if address == self.last_ppu_address {
+      self.repeated_reads += 1;
+
+      if self.repeated_reads == 2 {
+        self.start_scanline();
+      }
+    } else {
+      self.repeated_reads = 0;
+    }
+
+    self.last_ppu_address = address;
+  }
+
+  fn start_scanline(&mut self) {
+    if self.in_frame {
+      self.scanline = self.scanline.wrapping_add(1);
+
+      if self.scanline == self.irq_compare {
+        self.irq_status = true;
+        self.irq_line = self.irq_enabled;
+      }
+    } else {
+      self.in_frame = true;
+      self.scanline = 0;
+    }
+
+    self.tile_fetches = 0;
+  }
+
+  fn end_frame(&mut self) {
+    self.in_frame = false;
+    self.irq_line = false;
+  }
+
+  // tiles fetched before the end-of-line dummy reads are the first two of the next scanline
+  fn tile_column(&self) -> (u8, u8) {
+    let column = (self.tile_fetches + 2) % TILES_PER_SCANLINE;
+    let scanline = if column < 2 { self.scanline.wrapping_add(1) } else { self.scanline };
+
+    (column, scanline)
+  }
+
+  fn split_tile_for(&self, column: u8) -> bool {
+    if self.split_control & 0x80 == 0 || self.exram_mode > EXRAM_MODE_EXTENDED_ATTRIBUTES {
+      return false;
+    }
+
+    let threshold = self.split_control & 0x1f;
+
+    if self.split_control & 0x40 == 0 {
+      column < threshold
+    } else {
+      column >= threshold
+    }
+  }
+
+  fn split_y(&self, scanline: u8) -> u16 {
+    (self.split_scroll as u16 + scanline as u16) % 240
+  }
+
+  fn read_background_nametable(&mut self, address: u16) -> Option<u8> {
+    let offset = (address & 0x3ff) as usize;
+    let is_attribute = offset >= 0x3c0;
+
+    let (column, scanline) = self.tile_column();
+
+    if is_attribute {
+      // the attribute fetch finishes a tile
+      self.tile_fetches = (self.tile_fetches + 1) % TILES_PER_SCANLINE;
+    } else {
+      self.split_tile = if self.split_tile_for(column) { Some(scanline) } else { None };
+
+      if self.split_tile.is_none() && self.exram_mode == EXRAM_MODE_EXTENDED_ATTRIBUTES {
+        self.extended_attribute = self.exram[offset];
+      }
+    }
+
+    if let Some(scanline) = self.split_tile {
+      // the split region is drawn from ExRAM with its own vertical scroll
+      let y = self.split_y(scanline) as usize;
+      let column = (column as usize) & 0x1f;
+
+      return if is_attribute {
+        let attribute = self.exram[0x3c0 + (y / 32) * 8 + column / 4];
+        let shift = ((y / 16) & 1) * 4 + ((column / 2) & 1) * 2;
+
+        Some(((attribute >> shift) & 0b11) * 0b01010101)
+      } else {
+        Some(self.exram[(y / 8) * 32 + column])
+      };
+    }
+
+    if is_attribute && self.exram_mode == EXRAM_MODE_EXTENDED_ATTRIBUTES {
+      return Some((self.extended_attribute >> 6) * 0b01010101);
+    }
+
+    self.read_nametable_page(address)
+  }
+
+  // ExRAM pages, while ExRAM is in a nametable mode. The rest are mapped through nametable
+  fn read_nametable_page(&self, address: u16) -> Option<u8> {
+    if self.nametable_page(address) == EXRAM_NAMETABLE && self.exram_mode <= EXRAM_MODE_EXTENDED_ATTRIBUTES {
+      Some(self.exram[(address & 0x3ff) as usize])
+    } else {
+      None
+    }
+  }
+
+  fn read_register(&mut self, address: u16) -> Option<u8> {
+    match address {
+      0x5010 => Some(if self.pcm_read_mode { 1 } else { 0 }),
+      0x5015 => Some(self.audio_status & 0b11),
+      0x5204 => {
+        let status = (self.irq_status as u8) << 7 | (self.in_frame as u8) << 6;
+
+        self.irq_status = false;
+        self.irq_line = false;
+
+        Some(status)
+      }
+      0x5205 => Some((self.multiplicand as u16 * self.multiplier as u16) as u8),
+      0x5206 => Some(((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8),
+      0x5c00..=0x5fff if self.exram_mode >= EXRAM_MODE_READ_WRITE => Some(self.exram[(address - 0x5c00) as usize]),
+      _ => None
+    }
+  }
+
+  fn write_register(&mut self, address: u16, val: u8) {
+    match address {
+      0x5000 => self.pulse1.control.set(val),
+      0x5002 => self.pulse1.timer_low.set(val),
+      0x5003 => self.pulse1.write_timer_high(val),
+      0x5004 => self.pulse2.control.set(val),
+      0x5006 => self.pulse2.timer_low.set(val),
+      0x5007 => self.pulse2.write_timer_high(val),
+      0x5010 => self.pcm_read_mode = val & 0b1 == 1,
+      // zero can't be written, it's reserved for the IRQ in read mode
+      0x5011 if !self.pcm_read_mode && val != 0 => self.pcm_output = val,
+      0x5015 => {
+        self.audio_status = val;
+        self.pulse1.toggle(val & 0b1 != 0);
+        self.pulse2.toggle(val & 0b10 != 0);
+      }
+      0x5100 => {
+        self.prg_mode = val & 0b11;
+        self.update_prg_banks();
+      }
+      0x5101 => {
+        self.chr_mode = val & 0b11;
+      }
+      0x5102 | 0x5103 => self.prg_ram_protect[(address - 0x5102) as usize] = val,
+      0x5104 => self.exram_mode = val & 0b11,
+      0x5105 => self.nametable_mapping = val,
+      0x5106 => self.fill_tile = val,
+      0x5107 => self.fill_attribute = val & 0b11,
+      0x5113..=0x5117 => {
+        self.prg_registers[(address - 0x5113) as usize] = val;
+        self.update_prg_banks();
+      }
+      0x5120..=0x512b => {
+        let index = (address - 0x5120) as usize;
+
+        self.chr_registers[index] = self.chr_upper_bits << 8 | val as u16;
+        self.last_chr_set_background = index >= 8;
+      }
+      0x5130 => self.chr_upper_bits = (val & 0b11) as u16,
+      0x5200 => self.split_control = val,
+      0x5201 => self.split_scroll = val,
+      0x5202 => self.split_bank = val,
+      0x5203 => self.irq_compare = val,
+      0x5204 => {
+        self.irq_enabled = val & 0x80 != 0;
+        self.irq_line = self.irq_enabled && self.irq_status;
+      }
+      0x5205 => self.multiplicand = val,
+      0x5206 => self.multiplier = val,
+      0x5c00..=0x5fff => {
+        let offset = (address - 0x5c00) as usize;
+
+        match self.exram_mode {
+          // the PPU owns ExRAM in the nametable modes, so writes while it's rendering store zero
+          EXRAM_MODE_NAMETABLE | EXRAM_MODE_EXTENDED_ATTRIBUTES => {
+            self.exram[offset] = if self.in_frame { 0 } else { val };
+          }
+          EXRAM_MODE_READ_WRITE => self.exram[offset] = val,
+          _ => ()
+        }
+      }
+      _ => ()
+    }
+  }
+
+  fn tick_audio(&mut self, cycles: u16) {
+    for _ in 0..cycles {
+      // the pulse timers run at half the CPU clock, same as the APU's
+      if self.audio_half_cycle {
+        self.pulse1.tick(1);
+        self.pulse2.tick(1);
+      }
+      self.audio_half_cycle = !self.audio_half_cycle;
+
+      self.audio_frame_cycles += 1;
+
+      if self.audio_frame_cycles >= AUDIO_FRAME_CYCLES {
+        self.audio_frame_cycles = 0;
+
+        self.pulse1.clock_quarter_frame();
+        self.pulse1.clock_half_frame();
+        self.pulse2.clock_quarter_frame();
+        self.pulse2.clock_half_frame();
+      }
+    }
+  }
+}
+
+impl MapperActions for Mmc5 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x8000..=0xffff => match self.prg_banks[((address - 0x8000) as usize) / PRG_BANK_SIZE] {
+        PrgBank::Rom(bank) => Some(bank | (address as usize) & (PRG_BANK_SIZE - 1)),
+        PrgBank::Ram(_) => None
+      },
+      _ => None
+    }
+  }
+
+  fn read_chr(&mut self, address: u16, fetch: PpuFetch) -> Option<usize> {
+    if fetch == PpuFetch::Background {
+      self.watch_ppu_read(address);
+
+      if let Some(scanline) = self.split_tile {
+        let fine_y = (self.split_y(scanline) & 0b111) as usize;
+        let bank = self.split_bank as usize * 4 * CHR_BANK_SIZE;
+
+        return Some((bank + ((address as usize) & 0xff8 | fine_y)) % (self.chr_page_size * CHR_BANK_SIZE));
+      }
+
+      if self.exram_mode == EXRAM_MODE_EXTENDED_ATTRIBUTES {
+        let bank = ((self.chr_upper_bits as usize) << 6 | (self.extended_attribute & 0x3f) as usize) * 4;
+
+        return Some(self.chr_bank_address(bank + ((address as usize) & 0xfff) / CHR_BANK_SIZE) | (address as usize) & (CHR_BANK_SIZE - 1));
+      }
+    }
+
+    // with 8x16 sprites the background and sprites get their own banks, otherwise whichever set was written last wins
+    let use_background_set = match fetch {
+      PpuFetch::Background if self.tall_sprites => true,
+      PpuFetch::Sprite if self.tall_sprites => false,
+      _ => self.last_chr_set_background
+    };
+
+    Some(self.chr_bank((address as usize) / CHR_BANK_SIZE, use_background_set) | (address as usize) & (CHR_BANK_SIZE - 1))
+  }
+
+  fn read_nametable(&mut self, address: u16, fetch: PpuFetch) -> Option<u8> {
+    if fetch == PpuFetch::Background {
+      self.watch_ppu_read(address);
+
+      self.read_background_nametable(address)
+    } else {
+      self.read_nametable_page(address)
+    }
+  }
+
+  fn write_nametable(&mut self, address: u16, val: u8) -> bool {
+    if self.nametable_page(address) != EXRAM_NAMETABLE {
+      return false;
+    }
+
+    if self.exram_mode <= EXRAM_MODE_EXTENDED_ATTRIBUTES {
+      self.exram[(address & 0x3ff) as usize] = val;
+    }
+
+    true
+  }
+
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    match address {
+      0x5000..=0x5fff => self.read_register(address),
+      0x6000..=0x7fff => {
+        let bank = (self.prg_registers[0] as usize % self.prg_ram_page_size) * PRG_BANK_SIZE;
+
+        self.prg_ram.get(bank + (address - 0x6000) as usize).copied()
+      }
+      0x8000..=0xffff => {
+        // the CPU reading the NMI vector means the PPU has left the visible frame
+        if address == 0xfffa || address == 0xfffb {
+          self.end_frame();
+        }
+
+        match self.prg_banks[((address - 0x8000) as usize) / PRG_BANK_SIZE] {
+          PrgBank::Ram(bank) => self.prg_ram.get(bank + (address as usize & (PRG_BANK_SIZE - 1))).copied(),
+          PrgBank::Rom(_) => None
+        }
+      }
+      _ => None
+    }
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    match address {
+      // the MMC5 watches PPUCTRL for the sprite size, the write still goes to the PPU
+      0x2000 => {
+        self.tall_sprites = val & 0b100000 != 0;
+
+        false
+      }
+      0x5000..=0x5fff => {
+        self.write_register(address, val);
+
+        true
+      }
+      0x6000..=0x7fff => {
+        let bank = (self.prg_registers[0] as usize % self.prg_ram_page_size) * PRG_BANK_SIZE;
+
+        self.write_prg_ram(bank + (address - 0x6000) as usize, val);
+
+        true
+      }
+      0x8000..=0xffff => {
+        if let PrgBank::Ram(bank) = self.prg_banks[((address - 0x8000) as usize) / PRG_BANK_SIZE] {
+          self.write_prg_ram(bank + (address as usize & (PRG_BANK_SIZE - 1)), val);
+        }
+
+        true
+      }
+      _ => false
+    }
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    // the PPU going quiet also means it has left the frame
+    if self.ppu_reading {
+      self.idle_cycles = 0;
+    } else {
+      self.idle_cycles += cycles;
+
+      if self.idle_cycles >= IDLE_CYCLES_OUT_OF_FRAME {
+        self.end_frame();
+      }
+    }
+
+    self.ppu_reading = false;
+
+    self.tick_audio(cycles);
+  }
+
+  fn nametable(&self, slot: usize) -> Nametable {
+    match (self.nametable_mapping >> (slot * 2)) & 0b11 {
+      CIRAM_A => Nametable::CiramA,
+      CIRAM_B => Nametable::CiramB,
+      FILL_NAMETABLE => Nametable::Fill { tile: self.fill_tile, palette: self.fill_attribute },
+      // ExRAM reads back as zeros once it's out of the nametable modes
+      _ => Nametable::Fill { tile: 0, palette: 0 }
+    }
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq_line
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq_line = val;
+  }
+
+  fn audio_output(&self) -> f32 {
+    let pulse_out = self.pulse1.output() + self.pulse2.output();
+
+    // same curves the APU uses for its own pulses and DMC
+    let pulse = if pulse_out > 0.0 { 95.52 / (8128.0 / pulse_out + 100.0) } else { 0.0 };
+    let pcm = if self.pcm_output > 0 { 163.67 / (24329.0 / (self.pcm_output as f32 / 2.0) + 100.0) } else { 0.0 };
+
+    pulse + pcm
+  }
+
+  fn save_pending(&self) -> bool {
+    self.save_pending
+  }
+
+  // PRG-RAM lives on the mapper since it can be banked into $8000-$dfff as well
+  fn save_data(&mut self) -> Option<Vec<u8>> {
+    self.save_pending = false;
+
+    Some(self.prg_ram.clone())
+  }
+
+  fn load_save_data(&mut self, data: &[u8]) {
+    let length = data.len().min(self.prg_ram.len());
+
+    self.prg_ram[..length].copy_from_slice(&data[..length]);
+  }
+}
+
+impl MapperBoard for Mmc5 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(5, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Mmc5::load(cartridge))
+  }
+}
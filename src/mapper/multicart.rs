@@ -0,0 +1,252 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 16_384;
+const CHR_BANK_SIZE: usize = 8192;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MulticartBoard {
+  // 225: 52/64/72-in-1, everything is latched from the address
+  // see https://www.nesdev.org/wiki/INES_Mapper_225
+  Bmc225,
+  // 226: 42/76-in-1, two data registers at $8000 and $8001
+  // see https://www.nesdev.org/wiki/INES_Mapper_226
+  Bmc226,
+  // 227: 1200-in-1 and friends, latched from the address with an UNROM-like mode
+  // see https://www.nesdev.org/wiki/INES_Mapper_227
+  Bmc227,
+  // 228: Active Enterprises' Action 52 and Cheetahmen II
+  // see https://www.nesdev.org/wiki/INES_Mapper_228
+  Action52
+}
+
+// pirate multicarts whose whole state is latched by writes to $8000-$ffff. A reset
+// clears the latch, which brings back the menu in the first bank just like the
+// real carts. 225 and 228 also have four nibbles of RAM in the expansion area that
+// survive a reset, which some menus use to remember the last game.
+pub struct Multicart {
+  board: MulticartBoard,
+  registers: [u16; 2],
+  prg_page_size: usize,
+  chr_page_size: usize,
+  ram: [u8; 4]
+}
+
+impl Multicart {
+  pub fn load(cartridge: &mut Cartridge, board: MulticartBoard) -> Self {
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    Self {
+      board,
+      registers: [0; 2],
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      ram: [0; 4]
+    }
+  }
+
+  fn nrom_banks(bank: usize, is_16k: bool) -> [usize; 2] {
+    if is_16k {
+      [bank, bank]
+    } else {
+      [bank & !0b1, bank | 0b1]
+    }
+  }
+
+  // the 16K banks at $8000 and $c000, or None where nothing answers
+  fn prg_banks(&self) -> Option<[usize; 2]> {
+    let [first, second] = self.registers.map(|register| register as usize);
+
+    let banks = match self.board {
+      MulticartBoard::Bmc225 => {
+        let bank = ((first >> 6) & 0x3f) | ((first >> 8) & 0x40);
+
+        Self::nrom_banks(bank, first & 0x1000 != 0)
+      }
+      MulticartBoard::Bmc226 => {
+        let bank = (first & 0x1f) | ((first & 0x80) >> 2) | ((second & 0b1) << 6);
+
+        Self::nrom_banks(bank, first & 0x20 != 0)
+      }
+      MulticartBoard::Bmc227 => {
+        let bank = ((first >> 2) & 0x1f) | ((first & 0x100) >> 3);
+        let is_32k = first & 0b1 != 0;
+
+        if first & 0x80 != 0 {
+          Self::nrom_banks(bank, !is_32k)
+        } else {
+          // UNROM-like, with the fixed bank at either end of the 128K block
+          let switchable = if is_32k { bank & 0x3e } else { bank };
+          let fixed = if first & 0x200 != 0 { bank | 0b111 } else { bank & 0x38 };
+
+          [switchable, fixed]
+        }
+      }
+      MulticartBoard::Action52 => {
+        // the third 512K chip was never fitted, so dumps store the fourth chip in its place
+        let chip = match (first >> 11) & 0b11 {
+          2 => return None,
+          3 => 2,
+          chip => chip
+        };
+
+        Self::nrom_banks(chip * 32 + ((first >> 6) & 0x1f), first & 0x20 != 0)
+      }
+    };
+
+    Some(banks)
+  }
+
+  fn chr_bank(&self) -> usize {
+    let [first, second] = self.registers.map(|register| register as usize);
+
+    match self.board {
+      MulticartBoard::Bmc225 => (first & 0x3f) | ((first >> 8) & 0x40),
+      MulticartBoard::Action52 => ((first & 0x0f) << 2) | (second & 0b11),
+      _ => 0
+    }
+  }
+
+  fn ram_address(&self, address: u16) -> Option<usize> {
+    match (self.board, address) {
+      (MulticartBoard::Bmc225, 0x5800..=0x5fff) | (MulticartBoard::Action52, 0x4020..=0x5fff) => Some((address & 0b11) as usize),
+      _ => None
+    }
+  }
+}
+
+impl MapperActions for Multicart {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some((self.chr_bank() % self.chr_page_size) * CHR_BANK_SIZE + address as usize),
+      0x8000..=0xffff => {
+        let bank = self.prg_banks()?[((address - 0x8000) as usize) / PRG_ROM_BANK_SIZE];
+
+        Some((bank % self.prg_page_size) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1)))
+      }
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if address < 0x8000 {
+      return None;
+    }
+
+    match self.board {
+      MulticartBoard::Bmc225 | MulticartBoard::Bmc227 => self.registers[0] = address,
+      MulticartBoard::Bmc226 => self.registers[(address & 0b1) as usize] = val as u16,
+      MulticartBoard::Action52 => self.registers = [address, val as u16]
+    }
+
+    None
+  }
+
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    self.ram_address(address).map(|index| self.ram[index])
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    match self.ram_address(address) {
+      Some(index) => {
+        self.ram[index] = val & 0x0f;
+        true
+      }
+      None => false
+    }
+  }
+
+  fn reset(&mut self) {
+    self.registers = [0; 2];
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    let first = self.registers[0];
+
+    let horizontal = match self.board {
+      MulticartBoard::Bmc225 | MulticartBoard::Action52 => first & 0x2000 != 0,
+      MulticartBoard::Bmc226 => first & 0x40 == 0,
+      MulticartBoard::Bmc227 => first & 0b10 != 0
+    };
+
+    if horizontal { Mirroring::Horizontal } else { Mirroring::Vertical }
+  }
+}
+
+impl MapperBoard for Multicart {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(225, None), (226, None), (227, None), (228, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    let board = match cartridge.header.mapper {
+      225 => MulticartBoard::Bmc225,
+      226 => MulticartBoard::Bmc226,
+      227 => MulticartBoard::Bmc227,
+      _ => MulticartBoard::Action52
+    };
+
+    Box::new(Multicart::load(cartridge, board))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cartridge::Mirroring;
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn bmc225_latches_banks_from_the_address() {
+    let mut mapper = test_cartridge(225, 0, 128, 128).mapper;
+
+    // 16K mode, PRG bank 5 + 64, CHR bank 3 + 64, horizontal
+    mapper.mem_write(0x8000 | 0x4000 | 0x2000 | 0x1000 | (5 << 6) | 3, 0);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(69 * 0x4000));
+    assert_eq!(mapper.mem_read(0xc000), Some(69 * 0x4000));
+    assert_eq!(mapper.mem_read(0x0000), Some(67 * 0x2000));
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+
+    assert!(mapper.cpu_write(0x5802, 0xf7));
+    mapper.reset();
+
+    assert_eq!(mapper.mem_read(0xc000), Some(0x4000));
+    assert_eq!(mapper.cpu_read(0x5806), Some(0x07));
+  }
+
+  #[test]
+  fn bmc226_combines_both_registers() {
+    let mut mapper = test_cartridge(226, 0, 128, 0).mapper;
+
+    mapper.mem_write(0x8000, 0x80 | 0x03);
+    mapper.mem_write(0x8001, 0x01);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(98 * 0x4000));
+    assert_eq!(mapper.mem_read(0xc000), Some(99 * 0x4000));
+  }
+
+  #[test]
+  fn bmc227_fixes_the_last_bank_of_the_block() {
+    let mut mapper = test_cartridge(227, 0, 64, 0).mapper;
+
+    // UNROM-like, bank 10, last bank fixed
+    mapper.mem_write(0x8000 | 0x200 | (10 << 2), 0);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(10 * 0x4000));
+    assert_eq!(mapper.mem_read(0xc000), Some(15 * 0x4000));
+  }
+
+  #[test]
+  fn action52_skips_the_missing_chip() {
+    let mut mapper = test_cartridge(228, 0, 96, 64).mapper;
+
+    // chip 3, bank 4, 32K mode, CHR bank 5 * 4 + 2
+    mapper.mem_write(0x8000 | (3 << 11) | (4 << 6) | 5, 2);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(68 * 0x4000));
+    assert_eq!(mapper.mem_read(0xc000), Some(69 * 0x4000));
+    assert_eq!(mapper.mem_read(0x0000), Some(22 * 0x2000));
+
+    mapper.mem_write(0x8000 | (2 << 11), 0);
+    assert_eq!(mapper.mem_read(0x8000), None);
+  }
+}
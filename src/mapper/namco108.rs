@@ -0,0 +1,107 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+
+// Namco 108 (and Nintendo's DxROM), the MMC3's predecessor: the same bank select and
+// bank data pair at $8000/$8001, without the IRQ, the mode bits or mirroring control.
+// R0-R1 are 2K banks at $0000-$0fff, R2-R5 1K banks at $1000-$1fff, R6-R7 8K PRG banks
+// at $8000 and $a000, and the last two PRG banks are fixed.
+// see https://www.nesdev.org/wiki/INES_Mapper_206
+pub struct Namco108 {
+  bank_select: usize,
+  registers: [usize; 8],
+  prg_page_size: usize,
+  chr_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Namco108 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    Self {
+      bank_select: 0,
+      registers: [0; 8],
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+
+  fn chr_bank(&self, address: u16) -> usize {
+    let slot = (address as usize) / CHR_BANK_SIZE;
+
+    match slot {
+      0..=3 => (self.registers[slot / 2] & 0x3e) | (slot & 0b1),
+      _ => self.registers[slot - 2] & 0x3f
+    }
+  }
+
+  fn prg_bank(&self, address: u16) -> usize {
+    match address {
+      0x8000..=0x9fff => self.registers[6] & 0x0f,
+      0xa000..=0xbfff => self.registers[7] & 0x0f,
+      0xc000..=0xdfff => self.prg_page_size.saturating_sub(2),
+      _ => self.prg_page_size - 1
+    }
+  }
+}
+
+impl MapperActions for Namco108 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some((self.chr_bank(address) % self.chr_page_size) * CHR_BANK_SIZE + ((address as usize) & (CHR_BANK_SIZE - 1))),
+      0x8000..=0xffff => Some((self.prg_bank(address) % self.prg_page_size) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1))),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x8000..=0x9fff if address & 0b1 == 0 => self.bank_select = (val & 0b111) as usize,
+      0x8000..=0x9fff => self.registers[self.bank_select] = val as usize,
+      _ => ()
+    }
+
+    None
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Namco108 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(206, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Namco108::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_and_chr_banks() {
+    let mut mapper = test_cartridge(206, 0, 8, 8).mapper;
+
+    for (register, bank) in [(0, 9), (2, 17), (6, 3), (7, 4)] {
+      mapper.mem_write(0x8000, register);
+      mapper.mem_write(0x8001, bank);
+    }
+
+    // the low bit of the 2K registers is ignored
+    assert_eq!(mapper.mem_read(0x0000), Some(8 * 0x400));
+    assert_eq!(mapper.mem_read(0x0400), Some(9 * 0x400));
+    assert_eq!(mapper.mem_read(0x1001), Some(17 * 0x400 + 1));
+    assert_eq!(mapper.mem_read(0x8000), Some(3 * 0x2000));
+    assert_eq!(mapper.mem_read(0xa000), Some(4 * 0x2000));
+    assert_eq!(mapper.mem_read(0xc000), Some(14 * 0x2000));
+    assert_eq!(mapper.mem_read(0xe000), Some(15 * 0x2000));
+  }
+}
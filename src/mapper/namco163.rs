@@ -0,0 +1,356 @@
+use crate::cartridge::Cartridge;
+use crate::cpu::apu::FULL_VOLUME_PULSE;
+
+use super::{Mapper, MapperActions, MapperBoard, Nametable};
+
+const PRG_ROM_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+const INTERNAL_RAM_SIZE: usize = 128;
+
+// bank numbers from $e0 up select one of the console's own nametables instead of CHR-ROM
+const CIRAM_BANK: u8 = 0xe0;
+
+const IRQ_COUNTER_MAX: u16 = 0x7fff;
+
+// one wavetable channel is updated every 15 CPU cycles
+const AUDIO_CYCLES_PER_CHANNEL: u16 = 15;
+const CHANNEL_REGISTERS: usize = 0x40;
+
+// a channel's sample times its volume runs from -120 to 105, a 4-bit wave centred on 8
+// at 15 steps of volume. Namco's boards mix the chip hotter than the other expansions,
+// so 240 steps are given two pulses' worth
+const OUTPUT_LEVEL: f32 = FULL_VOLUME_PULSE * 2.0 / 240.0;
+
+// see https://www.nesdev.org/wiki/INES_Mapper_019 and https://www.nesdev.org/wiki/Namco_163_audio
+pub struct Namco163 {
+  prg_rom_banks: [usize; 3],
+  prg_page_size: usize,
+  // eight pattern table banks followed by the four nametables
+  chr_registers: [u8; 12],
+  chr_page_size: usize,
+  // boards with CHR-RAM can only map the console's nametables
+  has_chr_rom: bool,
+  // $e800 bits 6 and 7 stop banks from $e0 up mapping CIRAM into each pattern table
+  low_ciram_disabled: bool,
+  high_ciram_disabled: bool,
+  prg_ram_protect: u8,
+  internal_ram: Vec<u8>,
+  ram_address: u8,
+  auto_increment: bool,
+  irq_counter: u16,
+  irq_enabled: bool,
+  irq_pending: bool,
+  audio_disabled: bool,
+  audio_cycles: u16,
+  audio_channel: usize,
+  channel_outputs: [i16; 8],
+  has_battery: bool,
+  save_pending: bool
+}
+
+impl Namco163 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    Self {
+      prg_rom_banks: [0; 3],
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_registers: [0, 0, 0, 0, 0, 0, 0, 0, CIRAM_BANK, CIRAM_BANK + 1, CIRAM_BANK, CIRAM_BANK + 1],
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      has_chr_rom: !cartridge.chr_rom.is_empty(),
+      low_ciram_disabled: false,
+      high_ciram_disabled: false,
+      prg_ram_protect: 0,
+      internal_ram: vec![0; INTERNAL_RAM_SIZE],
+      ram_address: 0,
+      auto_increment: false,
+      irq_counter: 0,
+      irq_enabled: false,
+      irq_pending: false,
+      audio_disabled: false,
+      audio_cycles: 0,
+      audio_channel: 7,
+      channel_outputs: [0; 8],
+      has_battery: cartridge.header.has_battery,
+      save_pending: false
+    }
+  }
+
+  fn chr_bank_address(&self, register: usize) -> usize {
+    (self.chr_registers[register] as usize % self.chr_page_size) * CHR_BANK_SIZE
+  }
+
+  // writes need the high nibble set to 0100, then each low bit protects one 2K window
+  fn prg_ram_writable(&self, address: u16) -> bool {
+    let window = (address - 0x6000) / 0x800;
+
+    self.prg_ram_protect & 0xf0 == 0x40 && self.prg_ram_protect & (1 << window) == 0
+  }
+
+  fn read_internal_ram(&mut self) -> u8 {
+    let val = self.internal_ram[self.ram_address as usize];
+
+    self.advance_ram_address();
+
+    val
+  }
+
+  fn write_internal_ram(&mut self, val: u8) {
+    self.internal_ram[self.ram_address as usize] = val;
+
+    if self.has_battery {
+      self.save_pending = true;
+    }
+
+    self.advance_ram_address();
+  }
+
+  fn advance_ram_address(&mut self) {
+    if self.auto_increment {
+      self.ram_address = (self.ram_address + 1) & 0x7f;
+    }
+  }
+
+  fn enabled_channels(&self) -> usize {
+    ((self.internal_ram[0x7f] >> 4) & 0b111) as usize + 1
+  }
+
+  // the channels take turns, counting down from 7, so each one runs slower the more are enabled
+  fn clock_channel(&mut self, channel: usize) {
+    let base = CHANNEL_REGISTERS + channel * 8;
+    let ram = &mut self.internal_ram;
+
+    let frequency = ram[base] as u32 | (ram[base + 2] as u32) << 8 | (ram[base + 4] as u32 & 0b11) << 16;
+    let phase = ram[base + 1] as u32 | (ram[base + 3] as u32) << 8 | (ram[base + 5] as u32) << 16;
+    let length = 256 - (ram[base + 4] & 0xfc) as u32;
+
+    let phase = (phase + frequency) % (length << 16);
+
+    ram[base + 1] = phase as u8;
+    ram[base + 3] = (phase >> 8) as u8;
+    ram[base + 5] = (phase >> 16) as u8;
+
+    // samples are packed two to a byte, low nibble first
+    let sample_address = ((phase >> 16) + ram[base + 6] as u32) & 0xff;
+    let sample = (ram[(sample_address >> 1) as usize] >> ((sample_address & 0b1) * 4)) & 0x0f;
+
+    let volume = (ram[base + 7] & 0x0f) as i16;
+
+    self.channel_outputs[channel] = (sample as i16 - 8) * volume;
+  }
+
+  fn tick_audio(&mut self, cycles: u16) {
+    self.audio_cycles += cycles;
+
+    while self.audio_cycles >= AUDIO_CYCLES_PER_CHANNEL {
+      self.audio_cycles -= AUDIO_CYCLES_PER_CHANNEL;
+
+      let first_channel = 8 - self.enabled_channels();
+
+      if self.audio_channel < first_channel {
+        self.audio_channel = 7;
+      }
+
+      self.clock_channel(self.audio_channel);
+
+      self.audio_channel = if self.audio_channel <= first_channel { 7 } else { self.audio_channel - 1 };
+    }
+  }
+}
+
+impl MapperActions for Namco163 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_bank_address((address as usize) / CHR_BANK_SIZE) | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      0x8000..=0xdfff => Some(self.prg_rom_banks[((address - 0x8000) as usize) / PRG_ROM_BANK_SIZE] | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      0xe000..=0xffff => Some((self.prg_page_size - 1) * PRG_ROM_BANK_SIZE + ((address as usize) & (PRG_ROM_BANK_SIZE - 1))),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff if self.prg_ram_writable(address) => return Some((address - 0x6000) as usize),
+      0x8000..=0xdfff => self.chr_registers[((address - 0x8000) / 0x800) as usize] = val,
+      0xe000..=0xe7ff => {
+        self.prg_rom_banks[0] = ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE;
+        self.audio_disabled = val & 0x40 != 0;
+      }
+      0xe800..=0xefff => {
+        self.prg_rom_banks[1] = ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE;
+        self.low_ciram_disabled = val & 0x40 != 0;
+        self.high_ciram_disabled = val & 0x80 != 0;
+      }
+      0xf000..=0xf7ff => self.prg_rom_banks[2] = ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE,
+      0xf800..=0xffff => {
+        // the same register also sets the wavetable RAM address
+        self.prg_ram_protect = val;
+        self.ram_address = val & 0x7f;
+        self.auto_increment = val & 0x80 != 0;
+      }
+      _ => ()
+    }
+
+    None
+  }
+
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    match address {
+      0x4800..=0x4fff => Some(self.read_internal_ram()),
+      0x5000..=0x57ff => Some(self.irq_counter as u8),
+      0x5800..=0x5fff => Some((self.irq_counter >> 8) as u8 | if self.irq_enabled { 0x80 } else { 0 }),
+      _ => None
+    }
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    match address {
+      0x4800..=0x4fff => self.write_internal_ram(val),
+      0x5000..=0x57ff => {
+        self.irq_counter = (self.irq_counter & 0x7f00) | val as u16;
+        self.irq_pending = false;
+      }
+      0x5800..=0x5fff => {
+        self.irq_counter = (self.irq_counter & 0xff) | ((val as u16 & 0x7f) << 8);
+        self.irq_enabled = val & 0x80 != 0;
+        self.irq_pending = false;
+      }
+      _ => return false
+    }
+
+    true
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    if self.irq_enabled && self.irq_counter < IRQ_COUNTER_MAX {
+      self.irq_counter = (self.irq_counter + cycles).min(IRQ_COUNTER_MAX);
+
+      if self.irq_counter == IRQ_COUNTER_MAX {
+        self.irq_pending = true;
+      }
+    }
+
+    self.tick_audio(cycles);
+  }
+
+  fn audio_output(&self) -> f32 {
+    if self.audio_disabled {
+      return 0.0;
+    }
+
+    // the chip flips between channels too fast to hear, so they blend into their average
+    let channels = self.enabled_channels();
+    let output: i16 = self.channel_outputs[8 - channels..].iter().sum();
+
+    output as f32 / channels as f32 * OUTPUT_LEVEL
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq_pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq_pending = val;
+  }
+
+  fn save_pending(&self) -> bool {
+    self.save_pending
+  }
+
+  // the wavetable RAM doubles as save RAM on battery backed boards
+  fn save_data(&mut self) -> Option<Vec<u8>> {
+    if !self.has_battery {
+      return None;
+    }
+
+    self.save_pending = false;
+
+    Some(self.internal_ram.clone())
+  }
+
+  fn load_save_data(&mut self, data: &[u8]) {
+    let length = data.len().min(INTERNAL_RAM_SIZE);
+
+    self.internal_ram[..length].copy_from_slice(&data[..length]);
+  }
+
+  fn pattern_nametable(&self, address: u16) -> Option<Nametable> {
+    let register = (address as usize) / CHR_BANK_SIZE;
+    let ciram_disabled = if register < 4 { self.low_ciram_disabled } else { self.high_ciram_disabled };
+
+    match self.chr_registers[register] {
+      bank if bank < CIRAM_BANK || ciram_disabled => None,
+      bank if bank & 0b1 == 0 => Some(Nametable::CiramA),
+      _ => Some(Nametable::CiramB)
+    }
+  }
+
+  fn nametable(&self, slot: usize) -> Nametable {
+    let bank = self.chr_registers[8 + slot];
+
+    if bank < CIRAM_BANK && self.has_chr_rom {
+      Nametable::ChrRom(bank as usize % self.chr_page_size)
+    } else if bank & 0b1 == 0 {
+      Nametable::CiramA
+    } else {
+      Nametable::CiramB
+    }
+  }
+}
+
+impl MapperBoard for Namco163 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(19, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Namco163::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cpu::CPU;
+  use crate::mapper::{test_cartridge, Nametable};
+
+  #[test]
+  fn pattern_banks_from_e0_map_ciram_unless_disabled() {
+    let mut mapper = test_cartridge(19, 0, 8, 16).mapper;
+
+    mapper.mem_write(0x8000, 0xe1);
+    mapper.mem_write(0xa000, 0xe0);
+    mapper.mem_write(0x8800, 0xdf);
+
+    assert_eq!(mapper.pattern_nametable(0x0005), Some(Nametable::CiramB));
+    assert_eq!(mapper.pattern_nametable(0x0405), None);
+    assert_eq!(mapper.pattern_nametable(0x1005), Some(Nametable::CiramA));
+
+    // bit 6 of $e800 turns it off for $0000-$0fff and bit 7 for $1000-$1fff
+    mapper.mem_write(0xe800, 0x40);
+    assert_eq!(mapper.pattern_nametable(0x0005), None);
+    assert_eq!(mapper.pattern_nametable(0x1005), Some(Nametable::CiramA));
+
+    mapper.mem_write(0xe800, 0x80);
+    assert_eq!(mapper.pattern_nametable(0x0005), Some(Nametable::CiramB));
+    assert_eq!(mapper.pattern_nametable(0x1005), None);
+  }
+
+  #[test]
+  fn ppu_writes_reach_ciram_through_the_pattern_tables() {
+    let mut cpu = CPU::new();
+    cpu.load_game(test_cartridge(19, 0, 8, 16));
+
+    cpu.mem_write(0x8000, 0xe1);
+
+    cpu.mem_write(0x2006, 0x00);
+    cpu.mem_write(0x2006, 0x05);
+    cpu.mem_write(0x2007, 0x42);
+
+    assert_eq!(cpu.ppu.vram[0x405], 0x42);
+  }
+}
@@ -0,0 +1,92 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 32_768;
+const CHR_BANK_SIZE: usize = 4096;
+
+const PRG_RAM_SIZE: usize = 8192;
+
+// mapper 34's AVE half: 8K of PRG-RAM whose last three bytes double as the bank registers,
+// $7ffd for a 32K PRG bank and $7ffe/$7fff for the two 4K CHR banks
+// see https://www.nesdev.org/wiki/NINA-001
+pub struct Nina001 {
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  chr_banks: [usize; 2],
+  chr_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Nina001 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    Self {
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_banks: [0, CHR_BANK_SIZE],
+      chr_page_size: (cartridge.chr_rom.len() / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+}
+
+impl MapperActions for Nina001 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_banks[(address as usize) / CHR_BANK_SIZE] | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    match address {
+      0x7ffd => self.prg_rom_bank = ((val & 0b1) as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE,
+      0x7ffe | 0x7fff => {
+        self.chr_banks[(address - 0x7ffe) as usize] = ((val & 0x0f) as usize % self.chr_page_size) * CHR_BANK_SIZE;
+      }
+      _ => ()
+    }
+
+    // the registers don't stop the RAM underneath them from being written
+    match address {
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      _ => None
+    }
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Nina001 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(34, Some(1))];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Nina001::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_and_chr_banks() {
+    let mut mapper = test_cartridge(34, 1, 4, 8).mapper;
+
+    assert_eq!(mapper.mem_write(0x7ffd, 1), Some(0x1ffd));
+    mapper.mem_write(0x7ffe, 5);
+    mapper.mem_write(0x7fff, 12);
+
+    assert_eq!(mapper.mem_read(0x8000), Some(0x8000));
+    assert_eq!(mapper.mem_read(0x0abc), Some(5 * 0x1000 + 0xabc));
+    assert_eq!(mapper.mem_read(0x1abc), Some(12 * 0x1000 + 0xabc));
+  }
+}
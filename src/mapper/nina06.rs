@@ -0,0 +1,80 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard};
+
+const PRG_ROM_BANK_SIZE: usize = 32_768;
+const CHR_BANK_SIZE: usize = 8192;
+
+// AVE's NINA-03 and NINA-06, which decode their one register in the expansion area:
+// bit 3 picks a 32K PRG bank and bits 0-2 an 8K CHR bank
+// see https://www.nesdev.org/wiki/NINA-003-006
+pub struct Nina06 {
+  prg_rom_bank: usize,
+  prg_rom_page_size: usize,
+  chr_bank: usize,
+  chr_page_size: usize,
+  mirroring: Mirroring
+}
+
+impl Nina06 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    Self {
+      prg_rom_bank: 0,
+      prg_rom_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_bank: 0,
+      chr_page_size: (cartridge.chr_rom.len() / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring
+    }
+  }
+}
+
+impl MapperActions for Nina06 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_bank | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x8000..=0xffff => Some(self.prg_rom_bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1)),
+      _ => None
+    }
+  }
+
+  // the register answers anywhere in $4100-$5fff with A8 set
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    if !(0x4100..=0x5fff).contains(&address) || address & 0xe100 != 0x4100 {
+      return false;
+    }
+
+    self.prg_rom_bank = (((val >> 3) & 0b1) as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE;
+    self.chr_bank = ((val & 0b111) as usize % self.chr_page_size) * CHR_BANK_SIZE;
+
+    true
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Nina06 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(79, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Nina06::load(cartridge))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::mapper::test_cartridge;
+
+  #[test]
+  fn switches_prg_and_chr_banks() {
+    let mut mapper = test_cartridge(79, 0, 4, 8).mapper;
+
+    assert!(!mapper.cpu_write(0x4000, 0x0f));
+    assert!(!mapper.cpu_write(0x4200, 0x0f));
+    assert!(mapper.cpu_write(0x5f00, 0b1110));
+
+    assert_eq!(mapper.mem_read(0x8000), Some(0x8000));
+    assert_eq!(mapper.mem_read(0x0042), Some(6 * 0x2000 + 0x42));
+  }
+}
@@ -0,0 +1,93 @@
+use crate::cartridge::{Mirroring, Cartridge, nsf::NsfFile};
+
+use super::MapperActions;
+
+const PRG_ROM_BANK_SIZE: usize = 4096;
+const PRG_RAM_SIZE: usize = 8192;
+
+// NSF rips run on a virtual board: 8K of RAM at $6000 and 4K banks at $8000-$ffff
+// see https://www.nesdev.org/wiki/NSF#Bankswitching
+pub struct Nsf {
+  prg_rom_banks: [usize; 8],
+  prg_rom_page_size: usize,
+  bankswitched: bool
+}
+
+impl Nsf {
+  pub fn load(cartridge: &mut Cartridge, nsf: &NsfFile) -> Self {
+    let bankswitched = nsf.is_bankswitched();
+
+    // bankswitched data is padded out so the load address lands at the same offset
+    // in its bank, otherwise it sits at its absolute address in a flat 32K image
+    let padding = if bankswitched {
+      (nsf.load_address as usize) & (PRG_ROM_BANK_SIZE - 1)
+    } else {
+      (nsf.load_address as usize).saturating_sub(0x8000)
+    };
+
+    let mut prg_rom = vec![0; padding];
+    prg_rom.extend_from_slice(&nsf.data);
+
+    let bank_count = prg_rom.len().div_ceil(PRG_ROM_BANK_SIZE).max(8);
+    prg_rom.resize(bank_count * PRG_ROM_BANK_SIZE, 0);
+
+    cartridge.prg_rom = prg_rom;
+    cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+
+    let initial_banks = if bankswitched { nsf.bankswitch } else { [0, 1, 2, 3, 4, 5, 6, 7] };
+
+    let mut mapper = Self {
+      prg_rom_banks: [0; 8],
+      prg_rom_page_size: bank_count,
+      bankswitched
+    };
+
+    for (index, bank) in initial_banks.into_iter().enumerate() {
+      mapper.select_bank(index, bank);
+    }
+
+    mapper
+  }
+
+  fn select_bank(&mut self, index: usize, bank: u8) {
+    self.prg_rom_banks[index] = (bank as usize % self.prg_rom_page_size) * PRG_ROM_BANK_SIZE;
+  }
+}
+
+impl MapperActions for Nsf {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => {
+        let bank = self.prg_rom_banks[((address - 0x8000) as usize) / PRG_ROM_BANK_SIZE];
+
+        Some(bank | (address as usize) & (PRG_ROM_BANK_SIZE - 1))
+      }
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, _val: u8) -> Option<usize> {
+    match address {
+      0x6000..=0x7fff => Some((address - 0x6000) as usize),
+      _ => None
+    }
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    match address {
+      0x5ff8..=0x5fff => {
+        if self.bankswitched {
+          self.select_bank((address - 0x5ff8) as usize, val);
+        }
+
+        true
+      }
+      _ => false
+    }
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    Mirroring::Horizontal
+  }
+}
@@ -0,0 +1,401 @@
+use std::f64::consts::PI;
+
+// the VRC7's cut down YM2413: six FM channels of two operators each, 15 fixed
+// instruments and one custom patch.
+// see https://www.nesdev.org/wiki/VRC7_audio
+const CHANNELS: usize = 6;
+
+// the synth makes a sample every 72 cycles of its 3.58 MHz clock, i.e. every 36 CPU cycles
+const CPU_CYCLES_PER_SAMPLE: u16 = 36;
+const SAMPLE_RATE: f64 = 49_716.0;
+
+// envelope attenuation runs from 0 to 48 dB in 128 steps, with 16 bits of fraction per step
+const ENVELOPE_BITS: u32 = 23;
+const ENVELOPE_MAX: u32 = (1 << ENVELOPE_BITS) - 1;
+const ENVELOPE_DB: f64 = 48.0;
+const SILENT_DB: f64 = 96.0;
+
+// how far the modulator (and its own feedback) can push the phase at full output
+const MODULATION_DEPTH: f64 = 4.0 * PI;
+const FEEDBACK_DEPTH: f64 = 2.0 * PI;
+
+const AM_FREQUENCY: f64 = 3.7;
+const AM_DEPTH_DB: f64 = 4.8;
+const FM_FREQUENCY: f64 = 6.4;
+const FM_DEPTH_CENTS: f64 = 14.0;
+
+// frequency multipliers, doubled so the 1/2 entry stays an integer
+const MULTIPLIERS: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+// attenuation at octave 7 for the top four bits of the frequency, at 6 dB per octave
+const KEY_SCALE_LEVELS: [f64; 16] = [
+  0.0, 18.0, 24.0, 27.75, 30.0, 32.25, 33.75, 35.25,
+  36.0, 37.5, 38.25, 39.0, 39.75, 40.5, 41.25, 42.0
+];
+
+// the built in instruments, dumped from the chip
+const PATCHES: [[u8; 8]; 15] = [
+  [0x03, 0x21, 0x05, 0x06, 0xe8, 0x81, 0x42, 0x27],
+  [0x13, 0x41, 0x14, 0x0d, 0xd8, 0xf6, 0x23, 0x12],
+  [0x11, 0x11, 0x08, 0x08, 0xfa, 0xb2, 0x20, 0x12],
+  [0x31, 0x61, 0x0c, 0x07, 0xa8, 0x64, 0x61, 0x27],
+  [0x32, 0x21, 0x1e, 0x06, 0xe1, 0x76, 0x01, 0x28],
+  [0x02, 0x01, 0x06, 0x00, 0xa3, 0xe2, 0xf4, 0xf4],
+  [0x21, 0x61, 0x1d, 0x07, 0x82, 0x81, 0x11, 0x07],
+  [0x23, 0x21, 0x22, 0x17, 0xa2, 0x72, 0x01, 0x17],
+  [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+  [0xb5, 0x01, 0x0f, 0x0f, 0xa8, 0xa5, 0x51, 0x02],
+  [0x17, 0xc1, 0x24, 0x07, 0xf8, 0xf8, 0x22, 0x12],
+  [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+  [0x01, 0x02, 0xd3, 0x05, 0xc9, 0x95, 0x03, 0x02],
+  [0x61, 0x63, 0x0c, 0x00, 0x94, 0xc0, 0x33, 0xf6],
+  [0x21, 0x72, 0x0d, 0x00, 0xc1, 0xd5, 0x56, 0x06]
+];
+
+// one operator's half of an instrument patch
+struct OperatorPatch {
+  tremolo: bool,
+  vibrato: bool,
+  sustained: bool,
+  key_scale_rate: bool,
+  multiplier: u32,
+  key_scale_level: u8,
+  rectified: bool,
+  attack: u8,
+  decay: u8,
+  sustain_level: u8,
+  release: u8
+}
+
+impl OperatorPatch {
+  fn new(patch: &[u8; 8], carrier: bool) -> Self {
+    let index = carrier as usize;
+
+    Self {
+      tremolo: patch[index] & 0x80 != 0,
+      vibrato: patch[index] & 0x40 != 0,
+      sustained: patch[index] & 0x20 != 0,
+      key_scale_rate: patch[index] & 0x10 != 0,
+      multiplier: MULTIPLIERS[(patch[index] & 0x0f) as usize],
+      key_scale_level: patch[2 + index] >> 6,
+      rectified: patch[3] & (if carrier { 0x10 } else { 0x08 }) != 0,
+      attack: patch[4 + index] >> 4,
+      decay: patch[4 + index] & 0x0f,
+      sustain_level: patch[6 + index] >> 4,
+      release: patch[6 + index] & 0x0f
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EnvelopeStage {
+  Attack,
+  Decay,
+  Sustain,
+  Release,
+  Off
+}
+
+struct Operator {
+  phase: f64,
+  stage: EnvelopeStage,
+  attenuation: u32
+}
+
+impl Operator {
+  fn new() -> Self {
+    Self {
+      phase: 0.0,
+      stage: EnvelopeStage::Off,
+      attenuation: ENVELOPE_MAX
+    }
+  }
+
+  fn key_on(&mut self) {
+    self.phase = 0.0;
+    self.stage = EnvelopeStage::Attack;
+  }
+
+  fn key_off(&mut self) {
+    if self.stage != EnvelopeStage::Off {
+      self.stage = EnvelopeStage::Release;
+    }
+  }
+
+  // the per sample attenuation change for a 4 bit rate, sped up for higher notes
+  fn rate_increment(rate: u8, key_scale: u8) -> u32 {
+    if rate == 0 {
+      return 0;
+    }
+
+    let rate = (rate * 4 + key_scale).min(63);
+
+    (4 + (rate as u32 & 0b11)) << (rate >> 2)
+  }
+
+  fn update_envelope(&mut self, patch: &OperatorPatch, key_scale: u8, channel_sustain: bool) {
+    let key_scale = if patch.key_scale_rate { key_scale } else { key_scale >> 2 };
+
+    match self.stage {
+      EnvelopeStage::Attack => {
+        if patch.attack == 15 {
+          self.attenuation = 0;
+        } else {
+          // attack is exponential, fast at first and slowing as it nears full volume
+          let increment = Self::rate_increment(patch.attack, key_scale) as u64;
+          let step = ((self.attenuation as u64 * increment) >> 18) as u32 + (increment > 0) as u32;
+
+          self.attenuation = self.attenuation.saturating_sub(step);
+        }
+
+        if self.attenuation == 0 {
+          self.stage = EnvelopeStage::Decay;
+        }
+      }
+      EnvelopeStage::Decay => {
+        // every sustain level step is 3 dB, i.e. 8 envelope steps
+        let sustain_level = (patch.sustain_level as u32) << (ENVELOPE_BITS - 4);
+
+        self.attenuation += Self::rate_increment(patch.decay, key_scale);
+
+        if self.attenuation >= sustain_level {
+          self.attenuation = sustain_level;
+          self.stage = EnvelopeStage::Sustain;
+        }
+      }
+      EnvelopeStage::Sustain => {
+        // percussive instruments keep fading while the key is held
+        if !patch.sustained {
+          self.attenuation += Self::rate_increment(patch.release, key_scale);
+        }
+      }
+      EnvelopeStage::Release => {
+        let rate = if channel_sustain {
+          5
+        } else if patch.sustained {
+          patch.release
+        } else {
+          7
+        };
+
+        self.attenuation += Self::rate_increment(rate, key_scale);
+      }
+      EnvelopeStage::Off => ()
+    }
+
+    if self.attenuation >= ENVELOPE_MAX {
+      self.attenuation = ENVELOPE_MAX;
+
+      if self.stage == EnvelopeStage::Release || self.stage == EnvelopeStage::Sustain {
+        self.stage = EnvelopeStage::Off;
+      }
+    }
+  }
+
+  fn envelope_db(&self) -> f64 {
+    self.attenuation as f64 / ENVELOPE_MAX as f64 * ENVELOPE_DB
+  }
+
+  fn output(&self, patch: &OperatorPatch, attenuation_db: f64, modulation: f64) -> f64 {
+    if self.stage == EnvelopeStage::Off || attenuation_db >= SILENT_DB {
+      return 0.0;
+    }
+
+    let wave = (2.0 * PI * self.phase + modulation).sin();
+
+    // the rectified waveform drops the negative half of the sine
+    if patch.rectified && wave < 0.0 {
+      return 0.0;
+    }
+
+    wave * 10f64.powf(-attenuation_db / 20.0)
+  }
+}
+
+struct Channel {
+  frequency: u16,
+  block: u8,
+  key_on: bool,
+  sustain: bool,
+  instrument: u8,
+  volume: u8,
+  modulator: Operator,
+  carrier: Operator,
+  feedback: [f64; 2]
+}
+
+impl Channel {
+  fn new() -> Self {
+    Self {
+      frequency: 0,
+      block: 0,
+      key_on: false,
+      sustain: false,
+      instrument: 0,
+      volume: 0,
+      modulator: Operator::new(),
+      carrier: Operator::new(),
+      feedback: [0.0; 2]
+    }
+  }
+
+  fn key_scale(&self) -> u8 {
+    (self.block << 1) | (self.frequency >> 8) as u8
+  }
+
+  fn key_scale_level_db(&self, level: u8) -> f64 {
+    if level == 0 {
+      return 0.0;
+    }
+
+    let db = KEY_SCALE_LEVELS[(self.frequency >> 5) as usize] - 6.0 * (7 - self.block) as f64;
+
+    // levels 1-3 are 1.5, 3 and 6 dB per octave
+    db.max(0.0) / (1 << (3 - level)) as f64
+  }
+}
+
+pub struct Opll {
+  selected_register: u8,
+  custom_patch: [u8; 8],
+  channels: Vec<Channel>,
+  cycles: u16,
+  am_phase: f64,
+  fm_phase: f64,
+  output: f64
+}
+
+impl Default for Opll {
+  fn default() -> Self {
+    Self {
+      selected_register: 0,
+      custom_patch: [0; 8],
+      channels: (0..CHANNELS).map(|_| Channel::new()).collect(),
+      cycles: 0,
+      am_phase: 0.0,
+      fm_phase: 0.0,
+      output: 0.0
+    }
+  }
+}
+
+impl Opll {
+  pub fn reset(&mut self) {
+    *self = Self::default();
+  }
+
+  pub fn select_register(&mut self, val: u8) {
+    self.selected_register = val;
+  }
+
+  pub fn write_data(&mut self, val: u8) {
+    let register = self.selected_register;
+    let index = (register & 0x0f) as usize;
+
+    match register {
+      0x00..=0x07 => self.custom_patch[index] = val,
+      0x10..=0x15 => self.channels[index].frequency = (self.channels[index].frequency & 0x100) | val as u16,
+      0x20..=0x25 => {
+        let channel = &mut self.channels[index];
+        let key_on = val & 0x10 != 0;
+
+        channel.frequency = (channel.frequency & 0xff) | ((val as u16 & 0b1) << 8);
+        channel.block = (val >> 1) & 0b111;
+        channel.sustain = val & 0x20 != 0;
+
+        if key_on && !channel.key_on {
+          channel.modulator.key_on();
+          channel.carrier.key_on();
+          channel.feedback = [0.0; 2];
+        } else if !key_on && channel.key_on {
+          channel.modulator.key_off();
+          channel.carrier.key_off();
+        }
+
+        channel.key_on = key_on;
+      }
+      0x30..=0x35 => {
+        self.channels[index].instrument = val >> 4;
+        self.channels[index].volume = val & 0x0f;
+      }
+      _ => ()
+    }
+  }
+
+  fn patch(&self, instrument: u8) -> [u8; 8] {
+    match instrument {
+      0 => self.custom_patch,
+      _ => PATCHES[instrument as usize - 1]
+    }
+  }
+
+  pub fn tick(&mut self, cycles: u16) {
+    self.cycles += cycles;
+
+    while self.cycles >= CPU_CYCLES_PER_SAMPLE {
+      self.cycles -= CPU_CYCLES_PER_SAMPLE;
+      self.clock_sample();
+    }
+  }
+
+  fn clock_sample(&mut self) {
+    self.am_phase = (self.am_phase + AM_FREQUENCY / SAMPLE_RATE).fract();
+    self.fm_phase = (self.fm_phase + FM_FREQUENCY / SAMPLE_RATE).fract();
+
+    let tremolo_db = AM_DEPTH_DB * (1.0 - (2.0 * PI * self.am_phase).cos()) / 2.0;
+    let vibrato = 2f64.powf(FM_DEPTH_CENTS * (2.0 * PI * self.fm_phase).sin() / 1200.0);
+
+    let mut output = 0.0;
+
+    for index in 0..CHANNELS {
+      let patch = self.patch(self.channels[index].instrument);
+      let modulator_patch = OperatorPatch::new(&patch, false);
+      let carrier_patch = OperatorPatch::new(&patch, true);
+
+      let total_level = (patch[2] & 0x3f) as f64 * 0.75;
+      let feedback = patch[3] & 0b111;
+
+      let channel = &mut self.channels[index];
+      let key_scale = channel.key_scale();
+
+      channel.modulator.update_envelope(&modulator_patch, key_scale, channel.sustain);
+      channel.carrier.update_envelope(&carrier_patch, key_scale, channel.sustain);
+
+      // the phase advances by F * 2^block * multiplier out of a 19 bit counter each sample
+      let base_increment = ((channel.frequency as u32) << channel.block) as f64 / (1 << 19) as f64 / 2.0;
+
+      for (operator, operator_patch) in [(&mut channel.modulator, &modulator_patch), (&mut channel.carrier, &carrier_patch)] {
+        let increment = base_increment * operator_patch.multiplier as f64;
+
+        operator.phase = (operator.phase + if operator_patch.vibrato { increment * vibrato } else { increment }).fract();
+      }
+
+      let tremolo = |operator_patch: &OperatorPatch| if operator_patch.tremolo { tremolo_db } else { 0.0 };
+
+      let modulator_db = channel.modulator.envelope_db() + total_level
+        + channel.key_scale_level_db(modulator_patch.key_scale_level) + tremolo(&modulator_patch);
+
+      let feedback_modulation = if feedback == 0 {
+        0.0
+      } else {
+        (channel.feedback[0] + channel.feedback[1]) / 2.0 * FEEDBACK_DEPTH / (1 << (7 - feedback)) as f64
+      };
+
+      let modulator_output = channel.modulator.output(&modulator_patch, modulator_db, feedback_modulation);
+
+      channel.feedback = [channel.feedback[1], modulator_output];
+
+      let carrier_db = channel.carrier.envelope_db() + channel.volume as f64 * 3.0
+        + channel.key_scale_level_db(carrier_patch.key_scale_level) + tremolo(&carrier_patch);
+
+      output += channel.carrier.output(&carrier_patch, carrier_db, modulator_output * MODULATION_DEPTH);
+    }
+
+    self.output = output;
+  }
+
+  // sum of the channels' carriers, each between -1.0 and 1.0
+  pub fn output(&self) -> f64 {
+    self.output
+  }
+}
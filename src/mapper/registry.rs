@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use crate::cartridge::Cartridge;
+
+use super::{Mapper, Empty};
+use super::{
+  sxrom::Sxrom, uxrom::Uxrom, cnrom::Cnrom, txrom::Txrom, mmc5::Mmc5, axrom::Axrom, mmc2::Mmc2, vrc4::Vrc4, vrc6::Vrc6,
+  vrc7::Vrc7, namco163::Namco163, fme7::Fme7, bandai::Bandai, gxrom::Gxrom, color_dreams::ColorDreams, bnrom::Bnrom,
+  nina001::Nina001, nina06::Nina06, camerica::Camerica, namco108::Namco108, action53::Action53, multicart::Multicart
+};
+
+lazy_static! {
+  static ref BUILTIN: MapperRegistry = MapperRegistry::with_builtin_boards();
+}
+
+// builds a board's mapper, and can resize the cartridge's RAM to what the board has
+pub type MapperFactory = fn(&mut Cartridge) -> Mapper;
+
+// a board that can be looked up by its iNES/NES 2.0 mapper number
+pub trait MapperBoard {
+  // the mapper numbers handled, with a submapper when the entry only covers that one
+  const MAPPERS: &'static [(u16, Option<u8>)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper;
+}
+
+// which board to build for a mapper and submapper. Entries for a specific submapper
+// win over ones for the whole mapper, and registering a number again replaces it, so
+// boards from outside this crate can be added or take over built in ones.
+#[derive(Clone, Default)]
+pub struct MapperRegistry {
+  factories: HashMap<(u16, Option<u8>), MapperFactory>
+}
+
+impl MapperRegistry {
+  pub fn builtin() -> &'static MapperRegistry {
+    &BUILTIN
+  }
+
+  pub fn with_builtin_boards() -> Self {
+    let mut registry = MapperRegistry::default();
+
+    registry
+      .register::<Empty>()
+      .register::<Sxrom>()
+      .register::<Uxrom>()
+      .register::<Cnrom>()
+      .register::<Txrom>()
+      .register::<Mmc5>()
+      .register::<Axrom>()
+      .register::<Mmc2>()
+      .register::<ColorDreams>()
+      .register::<Bandai>()
+      .register::<Namco163>()
+      .register::<Vrc4>()
+      .register::<Vrc6>()
+      .register::<Action53>()
+      .register::<Bnrom>()
+      .register::<Nina001>()
+      .register::<Gxrom>()
+      .register::<Fme7>()
+      .register::<Camerica>()
+      .register::<Nina06>()
+      .register::<Vrc7>()
+      .register::<Namco108>()
+      .register::<Multicart>();
+
+    registry
+  }
+
+  pub fn register<T: MapperBoard>(&mut self) -> &mut Self {
+    for &(mapper, submapper) in T::MAPPERS {
+      self.factories.insert((mapper, submapper), T::create);
+    }
+
+    self
+  }
+
+  pub fn factory(&self, mapper: u16, submapper: u8) -> Option<MapperFactory> {
+    self.factories.get(&(mapper, Some(submapper)))
+      .or_else(|| self.factories.get(&(mapper, None)))
+      .copied()
+  }
+
+  pub fn supports(&self, mapper: u16, submapper: u8) -> bool {
+    self.factory(mapper, submapper).is_some()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cartridge::{Cartridge, Mirroring};
+  use crate::mapper::{Mapper, MapperActions, MapperBoard, MapperRegistry};
+
+  struct Experimental;
+
+  impl MapperActions for Experimental {
+    fn mirroring(&self) -> Mirroring {
+      Mirroring::FourScreen
+    }
+  }
+
+  impl MapperBoard for Experimental {
+    const MAPPERS: &'static [(u16, Option<u8>)] = &[(4, Some(15))];
+
+    fn create(_cartridge: &mut Cartridge) -> Mapper {
+      Box::new(Experimental)
+    }
+  }
+
+  #[test]
+  fn prefers_a_registered_submapper() {
+    let mut registry = MapperRegistry::with_builtin_boards();
+
+    registry.register::<Experimental>();
+
+    assert!(registry.supports(4, 0));
+    assert!(!registry.supports(4095, 0));
+
+    let mut rom = vec![0x4e, 0x45, 0x53, 0x1a, 2, 1, 0x40, 0x08, 0xf0];
+    rom.resize(16 + 2 * 16_384 + 8192, 0);
+
+    let cartridge = Cartridge::new_with_registry(rom, None, &registry).unwrap();
+
+    assert_eq!(cartridge.mapper.mirroring(), Mirroring::FourScreen);
+  }
+}
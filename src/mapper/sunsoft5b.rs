@@ -0,0 +1,229 @@
+// the 5B's sound half: a YM2149F (AY-3-8910 compatible) with three square
+// channels, a shared noise generator and a shared envelope.
+// see https://www.nesdev.org/wiki/Sunsoft_5B_audio
+
+// the chip runs at half the CPU clock and divides that by another 8 for its tone, noise and envelope steps
+const CPU_CYCLES_PER_STEP: u16 = 16;
+
+// each volume and envelope step is 1.5 dB
+const LEVEL_STEP_DB: f32 = 1.5;
+
+struct ToneChannel {
+  period: u16,
+  counter: u16,
+  output: bool,
+  volume: u8,
+  uses_envelope: bool
+}
+
+impl ToneChannel {
+  fn new() -> Self {
+    Self {
+      period: 0,
+      counter: 0,
+      output: false,
+      volume: 0,
+      uses_envelope: false
+    }
+  }
+
+  fn step(&mut self) {
+    self.counter += 1;
+
+    if self.counter >= self.period.max(1) {
+      self.counter = 0;
+      self.output = !self.output;
+    }
+  }
+}
+
+pub struct Sunsoft5b {
+  selected_register: u8,
+  channels: [ToneChannel; 3],
+  tone_disabled: u8,
+  noise_disabled: u8,
+  noise_period: u8,
+  noise_counter: u8,
+  noise_lfsr: u32,
+  envelope_period: u16,
+  envelope_counter: u16,
+  envelope_step: u8,
+  envelope_attack: bool,
+  envelope_continue: bool,
+  envelope_alternate: bool,
+  envelope_hold: bool,
+  envelope_holding: bool,
+  cycles: u16,
+  levels: [f32; 32]
+}
+
+impl Default for Sunsoft5b {
+  fn default() -> Self {
+    // level 0 is silent, every step above it 1.5 dB louder up to full scale at 31
+    let mut levels = [0.0; 32];
+
+    for (level, amplitude) in levels.iter_mut().enumerate().skip(1) {
+      *amplitude = 10f32.powf((level as f32 - 31.0) * LEVEL_STEP_DB / 20.0);
+    }
+
+    Self {
+      selected_register: 0,
+      channels: [ToneChannel::new(), ToneChannel::new(), ToneChannel::new()],
+      tone_disabled: 0,
+      noise_disabled: 0,
+      noise_period: 0,
+      noise_counter: 0,
+      noise_lfsr: 1,
+      envelope_period: 0,
+      envelope_counter: 0,
+      envelope_step: 31,
+      envelope_attack: false,
+      envelope_continue: false,
+      envelope_alternate: false,
+      envelope_hold: false,
+      envelope_holding: true,
+      cycles: 0,
+      levels
+    }
+  }
+}
+
+impl Sunsoft5b {
+  pub fn select_register(&mut self, val: u8) {
+    self.selected_register = val & 0x0f;
+  }
+
+  pub fn write_data(&mut self, val: u8) {
+    match self.selected_register {
+      register @ 0..=5 => {
+        let channel = &mut self.channels[(register / 2) as usize];
+
+        channel.period = if register % 2 == 0 {
+          (channel.period & 0xf00) | val as u16
+        } else {
+          (channel.period & 0xff) | ((val as u16 & 0x0f) << 8)
+        };
+      }
+      6 => self.noise_period = val & 0x1f,
+      7 => {
+        self.tone_disabled = val & 0b111;
+        self.noise_disabled = (val >> 3) & 0b111;
+      }
+      register @ 8..=10 => {
+        let channel = &mut self.channels[(register - 8) as usize];
+
+        channel.volume = val & 0x0f;
+        channel.uses_envelope = val & 0x10 != 0;
+      }
+      11 => self.envelope_period = (self.envelope_period & 0xff00) | val as u16,
+      12 => self.envelope_period = (self.envelope_period & 0xff) | (val as u16) << 8,
+      13 => {
+        self.envelope_continue = val & 0b1000 != 0;
+        self.envelope_attack = val & 0b100 != 0;
+        self.envelope_alternate = val & 0b10 != 0;
+        self.envelope_hold = val & 0b1 != 0;
+
+        self.envelope_step = 0;
+        self.envelope_counter = 0;
+        self.envelope_holding = false;
+      }
+      _ => ()
+    }
+  }
+
+  fn envelope_level(&self) -> u8 {
+    if self.envelope_attack { self.envelope_step } else { 31 - self.envelope_step }
+  }
+
+  fn step_envelope(&mut self) {
+    self.envelope_counter += 1;
+
+    if self.envelope_counter < self.envelope_period.max(1) {
+      return;
+    }
+
+    self.envelope_counter = 0;
+
+    if self.envelope_holding {
+      return;
+    }
+
+    if self.envelope_step < 31 {
+      self.envelope_step += 1;
+      return;
+    }
+
+    // end of a ramp: the shape decides whether to hold, flip direction or start over
+    if !self.envelope_continue {
+      self.envelope_attack = false;
+      self.envelope_holding = true;
+    } else if self.envelope_hold {
+      if self.envelope_alternate {
+        self.envelope_attack = !self.envelope_attack;
+      }
+
+      self.envelope_holding = true;
+    } else {
+      if self.envelope_alternate {
+        self.envelope_attack = !self.envelope_attack;
+      }
+
+      self.envelope_step = 0;
+    }
+  }
+
+  fn step_noise(&mut self) {
+    // noise runs at half the tone rate
+    self.noise_counter += 1;
+
+    if self.noise_counter < self.noise_period.max(1) * 2 {
+      return;
+    }
+
+    self.noise_counter = 0;
+
+    let feedback = (self.noise_lfsr ^ (self.noise_lfsr >> 3)) & 0b1;
+
+    self.noise_lfsr = (self.noise_lfsr >> 1) | (feedback << 16);
+  }
+
+  pub fn tick(&mut self, cycles: u16) {
+    self.cycles += cycles;
+
+    while self.cycles >= CPU_CYCLES_PER_STEP {
+      self.cycles -= CPU_CYCLES_PER_STEP;
+
+      for channel in self.channels.iter_mut() {
+        channel.step();
+      }
+
+      self.step_noise();
+      self.step_envelope();
+    }
+  }
+
+  // the three channels summed, each from 0.0 to 1.0
+  pub fn output(&self) -> f32 {
+    let noise = self.noise_lfsr & 0b1 != 0;
+
+    self.channels.iter().enumerate().map(|(index, channel)| {
+      let tone_on = channel.output || self.tone_disabled & (1 << index) != 0;
+      let noise_on = noise || self.noise_disabled & (1 << index) != 0;
+
+      if !(tone_on && noise_on) {
+        return 0.0;
+      }
+
+      let level = if channel.uses_envelope {
+        self.envelope_level()
+      } else if channel.volume == 0 {
+        0
+      } else {
+        // the 4 bit volume lines up with every other envelope step
+        channel.volume * 2 + 1
+      };
+
+      self.levels[level as usize]
+    }).sum()
+  }
+}
@@ -1,6 +1,6 @@
 use crate::cartridge::{Cartridge, Mirroring};
 
-use super::{MapperActions, BankType};
+use super::{Mapper, MapperActions, MapperBoard, BankType};
 
 const PRG_RAM_SIZE: usize = 8192;
 const CHR_RAM_SIZE: usize = 8192;
@@ -10,7 +10,7 @@ const _PRG_RAM_BANK_SIZE: usize = 8192;
 const PRG_ROM_BANK_SIZE: usize = 16_384;
 
 struct SxromRegisters {
-  write_occurred: i8,
+  write_occurred: u16,
   shift: u8,
   control: u8,
   chr0: u8,
@@ -33,8 +33,12 @@ pub struct Sxrom {
 
 impl Sxrom {
   pub fn load(cartridge: &mut Cartridge, ram_always_on: bool) -> Self {
-    cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
-    cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+    if cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
 
     let chr_banks: [usize; 2] = [0, CHR_BANK_SIZE];
     let prg_rom_banks: [usize; 2] = [0, PRG_ROM_BANK_SIZE];
@@ -195,10 +199,6 @@ impl Sxrom {
       _ => panic!("not possible")
     }
   }
-
-  pub fn mirroring(&self) -> Mirroring {
-    self.mirroring
-  }
 }
 
 impl MapperActions for Sxrom {
@@ -211,10 +211,12 @@ impl MapperActions for Sxrom {
     }
   }
 
-  fn tick(&mut self, cycles: u8) {
-    if self.registers.write_occurred > 0 {
-      self.registers.write_occurred -= cycles as i8;
-    }
+  fn tick(&mut self, cycles: u16) {
+    self.registers.write_occurred = self.registers.write_occurred.saturating_sub(cycles);
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
   }
 
   fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
@@ -258,3 +260,11 @@ impl MapperActions for Sxrom {
 
   }
 }
+
+impl MapperBoard for Sxrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(1, None), (155, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Sxrom::load(cartridge, cartridge.header.mapper == 155))
+  }
+}
@@ -1,21 +1,46 @@
 use crate::cartridge::{Cartridge, Mirroring};
 
-use super::{MapperActions, BankType};
+use super::{Mapper, MapperActions, MapperBoard, BankType, Nametable};
 
 const PRG_ROM_BANK_SIZE: usize = 8192;
 const CHR_BANK_SIZE: usize = 1024;
 
 const CHR_RAM_SIZE: usize = 8192;
 const PRG_RAM_SIZE: usize = 8192;
+const MMC6_PRG_RAM_SIZE: usize = 1024;
+
+// A12 has to stay low for a few M2 cycles before a rise clocks the scanline counter, which
+// keeps the sprite fetches between nametable reads from clocking it eight times a line
+const A12_FILTER_CYCLES: u64 = 3;
+
+// the boards built around the MMC3 that wire it differently
+// see https://www.nesdev.org/wiki/MMC3, https://www.nesdev.org/wiki/MMC6,
+// https://www.nesdev.org/wiki/INES_Mapper_118 and https://www.nesdev.org/wiki/INES_Mapper_119
+#[derive(Clone, Copy, PartialEq)]
+pub enum TxromBoard {
+  Mmc3,
+  // 1K of PRG-RAM inside the chip, split in two halves with their own enables in $a001
+  Mmc6,
+  // TKSROM/TLSROM: bit 7 of the CHR banks picks the nametable instead of $a000
+  Txsrom,
+  // bit 6 of the CHR banks switches between CHR-ROM and 8K of CHR-RAM
+  Tqrom
+}
 
 pub struct Txrom {
+  board: TxromBoard,
   prg_rom_banks: [usize; 4],
   chr_banks: [usize; 8],
   mirroring: Mirroring,
   registers: TxromRegisters,
-  prg_page_size: u8,
-  chr_page_size: u8,
-  irq_pending: bool
+  prg_page_size: usize,
+  chr_page_size: usize,
+  chr_rom_len: usize,
+  irq_pending: bool,
+  a12_high: bool,
+  a12_fell_at: u64,
+  prg_ram_enabled: bool,
+  prg_ram_protect: u8
 }
 
 struct TxromRegisters {
@@ -24,37 +49,51 @@ struct TxromRegisters {
   irq_latch: u8,
   irq_counter: u8,
   irq_reload: bool,
-  irq_enable: bool,
-  last_clock: u8
+  irq_enable: bool
 }
 
 impl Txrom {
-  pub fn load(cartridge: &mut Cartridge) -> Self {
-    let chr_len = if cartridge.chr_rom.is_empty() {
+  pub fn load(cartridge: &mut Cartridge, board: TxromBoard) -> Self {
+    if board == TxromBoard::Tqrom && cartridge.chr_ram.len() < CHR_RAM_SIZE {
       cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
+
+    let chr_len = if cartridge.chr_rom.is_empty() {
+      if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+        cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+      }
 
-      CHR_RAM_SIZE
+      cartridge.chr_ram.len()
     } else {
       cartridge.chr_rom.len()
     };
 
-    cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    let prg_ram_size = if board == TxromBoard::Mmc6 { MMC6_PRG_RAM_SIZE } else { PRG_RAM_SIZE };
+
+    if cartridge.prg_ram.len() < prg_ram_size {
+      cartridge.prg_ram.resize(prg_ram_size, 0);
+    }
 
     let mut txrom = Self {
+      board,
       prg_rom_banks: [0; 4],
       chr_banks: [0; 8],
       mirroring: cartridge.mirroring,
-      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE) as u8,
-      chr_page_size: (chr_len / CHR_BANK_SIZE) as u8,
+      prg_page_size: cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE,
+      chr_page_size: chr_len / CHR_BANK_SIZE,
+      chr_rom_len: cartridge.chr_rom.len(),
       irq_pending: false,
+      a12_high: false,
+      a12_fell_at: 0,
+      prg_ram_enabled: false,
+      prg_ram_protect: 0,
       registers: TxromRegisters {
         bank_select: 0,
         bank_data: [0; 8],
         irq_latch: 0,
         irq_reload: false,
         irq_enable: false,
-        irq_counter: 0,
-        last_clock: 0
+        irq_counter: 0
       }
     };
 
@@ -65,47 +104,51 @@ impl Txrom {
   }
 
   fn update_chr_banks(&mut self, mode: u8) {
-    if mode == 1 {
-      self.chr_banks[4] = self.get_bank_address(self.registers.bank_data[0], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[5] = self.get_bank_address(self.registers.bank_data[0] + 1, self.chr_page_size, CHR_BANK_SIZE);
-
-      self.chr_banks[6] = self.get_bank_address(self.registers.bank_data[1], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[7] = self.get_bank_address(self.registers.bank_data[1] + 1, self.chr_page_size, CHR_BANK_SIZE);
+    // the 2K banks ignore their low bit
+    let banks = [
+      self.registers.bank_data[0] & 0xfe,
+      self.registers.bank_data[0] | 1,
+      self.registers.bank_data[1] & 0xfe,
+      self.registers.bank_data[1] | 1,
+      self.registers.bank_data[2],
+      self.registers.bank_data[3],
+      self.registers.bank_data[4],
+      self.registers.bank_data[5]
+    ];
+
+    // mode 1 swaps the 2K and 1K halves
+    let offset = if mode == 1 { 4 } else { 0 };
+
+    for (index, bank) in banks.iter().enumerate() {
+      self.chr_banks[(index + offset) % 8] = self.chr_bank_address(*bank);
+    }
+  }
 
-      self.chr_banks[0] = self.get_bank_address(self.registers.bank_data[2], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[1] = self.get_bank_address(self.registers.bank_data[3], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[2] = self.get_bank_address(self.registers.bank_data[4], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[3] = self.get_bank_address(self.registers.bank_data[5], self.chr_page_size, CHR_BANK_SIZE);
+  // TQROM's CHR-RAM sits after CHR-ROM, where the PPU looks for it
+  fn chr_bank_address(&self, bank: u8) -> usize {
+    if self.board == TxromBoard::Tqrom && bank & 0x40 != 0 {
+      self.chr_rom_len + ((bank & 0b111) as usize) * CHR_BANK_SIZE
     } else {
-      self.chr_banks[0] = self.get_bank_address(self.registers.bank_data[0], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[1] = self.get_bank_address(self.registers.bank_data[0] + 1, self.chr_page_size, CHR_BANK_SIZE);
-
-      self.chr_banks[2] = self.get_bank_address(self.registers.bank_data[1], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[3] = self.get_bank_address(self.registers.bank_data[1] + 1, self.chr_page_size, CHR_BANK_SIZE);
-
-      self.chr_banks[4] = self.get_bank_address(self.registers.bank_data[2], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[5] = self.get_bank_address(self.registers.bank_data[3], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[6] = self.get_bank_address(self.registers.bank_data[4], self.chr_page_size, CHR_BANK_SIZE);
-      self.chr_banks[7] = self.get_bank_address(self.registers.bank_data[5], self.chr_page_size, CHR_BANK_SIZE);
+      self.get_bank_address(bank as usize, self.chr_page_size, CHR_BANK_SIZE)
     }
   }
 
-  fn get_bank_address(&self, bank: u8, page_size: u8, bank_size: usize) -> usize {
-    (bank % page_size) as usize * bank_size
+  fn get_bank_address(&self, bank: usize, page_size: usize, bank_size: usize) -> usize {
+    (bank % page_size) * bank_size
   }
 
   fn update_prg_banks(&mut self, mode: u8) {
 
     // swap bank at a000-bfff (bank 2)
-    self.prg_rom_banks[1] = self.get_bank_address(self.registers.bank_data[7], self.prg_page_size, PRG_ROM_BANK_SIZE);
+    self.prg_rom_banks[1] = self.get_bank_address(self.registers.bank_data[7] as usize, self.prg_page_size, PRG_ROM_BANK_SIZE);
     if mode == 0 {
       // bank at 0x8000-9fff (bank 1) swappable,
-      self.prg_rom_banks[0] = self.get_bank_address(self.registers.bank_data[6], self.prg_page_size, PRG_ROM_BANK_SIZE);
+      self.prg_rom_banks[0] = self.get_bank_address(self.registers.bank_data[6] as usize, self.prg_page_size, PRG_ROM_BANK_SIZE);
       // bank at c000-dfff (bank 3) fixed to second to last bank
       self.prg_rom_banks[2] = self.get_bank_address(self.prg_page_size - 2, self.prg_page_size, PRG_ROM_BANK_SIZE);
     } else {
       // c000-dfff (bank 3) swappable,
-      self.prg_rom_banks[2] = self.get_bank_address(self.registers.bank_data[6], self.prg_page_size, PRG_ROM_BANK_SIZE);
+      self.prg_rom_banks[2] = self.get_bank_address(self.registers.bank_data[6] as usize, self.prg_page_size, PRG_ROM_BANK_SIZE);
       // 8000-9fff (bank 1) fixed to second to last bank
       self.prg_rom_banks[0] = self.get_bank_address(self.prg_page_size - 2, self.prg_page_size, PRG_ROM_BANK_SIZE);
     }
@@ -141,25 +184,60 @@ impl Txrom {
     self.update_chr_banks(chr_mode);
   }
 
-  fn clock_irq(&mut self, address: u16) {
-    if address < 0x2000 {
-      let next_clock = ((address >> 12) & 0b1) as u8;
-      let last: u8 = 0;
-      let next: u8 = 1;
+  // MMC6 RAM is 1K mirrored through $7000-$7fff, each 512 byte half needing its read enable
+  // (and write enable for writes) set in $a001
+  fn mmc6_ram_address(&self, address: u16, write: bool) -> Option<usize> {
+    if !self.prg_ram_enabled || address < 0x7000 {
+      return None;
+    }
 
-      if self.registers.last_clock == last && next_clock == next {
-        let counter = self.registers.irq_counter;
-        if self.registers.irq_counter == 0 || self.registers.irq_reload {
-          self.registers.irq_counter = self.registers.irq_latch;
-        } else {
-          self.registers.irq_counter -= 1;
-        }
-        if ((counter & 0b1) == 1 || self.registers.irq_reload) && self.registers.irq_enable && self.registers.irq_counter == 0 {
-          self.irq_pending = true
-        }
-        self.registers.irq_reload = false;
-      }
-      self.registers.last_clock = next_clock;
+    let shift = if address & 0x200 != 0 { 6 } else { 4 };
+    let readable = self.prg_ram_protect & (0b10 << shift) != 0;
+    let writable = self.prg_ram_protect & (0b1 << shift) != 0;
+
+    if readable && (!write || writable) {
+      Some((address & 0x3ff) as usize)
+    } else {
+      None
+    }
+  }
+
+  // TxSROM routes CHR A17 to CIRAM A10, so each nametable follows a CHR bank
+  fn txsrom_nametable(&self, slot: usize) -> Nametable {
+    let bank_data = &self.registers.bank_data;
+
+    let bank = if self.registers.bank_select >> 7 == 0 {
+      bank_data[slot / 2]
+    } else {
+      bank_data[2 + slot]
+    };
+
+    if bank >> 7 == 0 { Nametable::CiramA } else { Nametable::CiramB }
+  }
+
+  // see https://www.nesdev.org/wiki/MMC3#IRQ_Specifics
+  fn watch_a12(&mut self, address: u16, m2_cycle: u64) {
+    let a12_high = address & 0x1000 != 0;
+
+    if a12_high && !self.a12_high && m2_cycle.wrapping_sub(self.a12_fell_at) >= A12_FILTER_CYCLES {
+      self.clock_irq();
+    } else if !a12_high && self.a12_high {
+      self.a12_fell_at = m2_cycle;
+    }
+
+    self.a12_high = a12_high;
+  }
+
+  fn clock_irq(&mut self) {
+    if self.registers.irq_counter == 0 || self.registers.irq_reload {
+      self.registers.irq_counter = self.registers.irq_latch;
+      self.registers.irq_reload = false;
+    } else {
+      self.registers.irq_counter -= 1;
+    }
+
+    if self.registers.irq_counter == 0 && self.registers.irq_enable {
+      self.irq_pending = true;
     }
   }
 }
@@ -174,17 +252,26 @@ impl MapperActions for Txrom {
       self.irq_pending = val;
   }
 
-  fn ppu_bus_write(&mut self, address: u16, _val: u8) {
-    self.clock_irq(address);
+  fn ppu_bus_address(&mut self, address: u16, m2_cycle: u64) {
+    self.watch_a12(address, m2_cycle);
   }
 
   fn mirroring(&self) -> Mirroring {
     self.mirroring
   }
 
+  fn nametable(&self, slot: usize) -> Nametable {
+    if self.board == TxromBoard::Txsrom {
+      return self.txsrom_nametable(slot);
+    }
+
+    Nametable::from_mirroring(self.mirroring, slot)
+  }
+
   fn mem_read(&mut self, address: u16) -> Option<usize> {
     match address {
       0x0000..=0x1fff => self.translate_address(address, BankType::Chr),
+      0x6000..=0x7fff if self.board == TxromBoard::Mmc6 => self.mmc6_ram_address(address, false),
       0x6000..=0x7fff => Some((address - 0x6000) as usize),
       0x8000..=0xffff => self.translate_address(address, BankType::Prg),
       _ => panic!("not possible")
@@ -193,9 +280,18 @@ impl MapperActions for Txrom {
   fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
     match address {
       0x0000..=0x1fff => self.translate_address(address, BankType::Chr),
+      0x6000..=0x7fff if self.board == TxromBoard::Mmc6 => self.mmc6_ram_address(address, true),
       0x6000..=0x7fff => Some((address - 0x6000) as usize),
       0x8000..=0x9fff => {
         if address %2 == 0 {
+          if self.board == TxromBoard::Mmc6 {
+            self.prg_ram_enabled = val & 0x20 != 0;
+
+            if !self.prg_ram_enabled {
+              self.prg_ram_protect = 0;
+            }
+          }
+
           self.registers.bank_select = val;
           self.update_banks();
         } else {
@@ -206,12 +302,19 @@ impl MapperActions for Txrom {
       }
       0xa000..=0xbfff => {
         if address %2 == 0 {
-          self.mirroring = if (val & 0b1) == 0 {
-            Mirroring::Vertical
-          } else {
-            Mirroring::Horizontal
-          };
+          // four-screen boards like Gauntlet don't wire up the mirroring bit
+          if self.mirroring != Mirroring::FourScreen {
+            self.mirroring = if (val & 0b1) == 0 {
+              Mirroring::Vertical
+            } else {
+              Mirroring::Horizontal
+            };
+          }
           self.update_banks();
+        } else if self.board == TxromBoard::Mmc6 {
+          if self.prg_ram_enabled {
+            self.prg_ram_protect = val & 0xf0;
+          }
         } else {
           // per https://www.nesdev.org/wiki/MMC3,
           // "Many emulators choose not to implement them as part of iNES Mapper 4 to avoid an incompatibility with the MMC6."
@@ -239,4 +342,146 @@ impl MapperActions for Txrom {
       _ => None
     }
   }
-}
\ No newline at end of file
+}
+
+impl MapperBoard for Txrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(4, None), (118, None), (119, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    let board = match (cartridge.header.mapper, cartridge.header.submapper) {
+      (4, 1) => TxromBoard::Mmc6,
+      (118, _) => TxromBoard::Txsrom,
+      (119, _) => TxromBoard::Tqrom,
+      _ => TxromBoard::Mmc3
+    };
+
+    Box::new(Txrom::load(cartridge, board))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cartridge::Cartridge;
+  use crate::cpu::CPU;
+  use crate::mapper::{test_cartridge, Nametable};
+
+  #[test]
+  fn mmc6_ram_halves_have_their_own_enables() {
+    let mut mapper = test_cartridge(4, 1, 8, 16).mapper;
+
+    // the RAM is off until $8000 bit 5 is set
+    mapper.mem_write(0xa001, 0xf0);
+    assert_eq!(mapper.mem_read(0x7000), None);
+
+    mapper.mem_write(0x8000, 0x20);
+
+    // $a001 bits 7/6 are the read/write enables for $7200-$73ff, bits 5/4 for $7000-$71ff
+    mapper.mem_write(0xa001, 0x30);
+    assert_eq!(mapper.mem_read(0x7005), Some(0x005));
+    assert_eq!(mapper.mem_write(0x7005, 0), Some(0x005));
+    assert_eq!(mapper.mem_read(0x7205), None);
+    assert_eq!(mapper.mem_write(0x7205, 0), None);
+
+    // readable but not writable, mirrored every 1K
+    mapper.mem_write(0xa001, 0xa0);
+    assert_eq!(mapper.mem_read(0x7e05), Some(0x205));
+    assert_eq!(mapper.mem_write(0x7205, 0), None);
+    assert_eq!(mapper.mem_write(0x7005, 0), None);
+  }
+
+  #[test]
+  fn mmc6_disabled_halves_read_as_zero() {
+    let mut cpu = CPU::new();
+    cpu.load_game(test_cartridge(4, 1, 8, 16));
+
+    cpu.mem_write(0x8000, 0x20);
+    cpu.mem_write(0xa001, 0xf0);
+    cpu.mem_write(0x7000, 0x55);
+    cpu.mem_write(0x7200, 0x66);
+
+    cpu.mem_write(0xa001, 0x30);
+
+    assert_eq!(cpu.mem_read(0x7000), 0x55);
+    assert_eq!(cpu.mem_read(0x7200), 0);
+  }
+
+  #[test]
+  fn tqrom_bit_6_banks_map_past_chr_rom() {
+    let mut cpu = CPU::new();
+    cpu.load_game(test_cartridge(119, 0, 8, 8));
+
+    let chr_rom_len = cpu.ppu.chr_rom.len();
+
+    // R2 is the 1K bank at $1000, with bit 6 switching it to CHR-RAM
+    for (bank, offset) in [(0x40, 0x005), (0x43, 3 * 1024 + 0x005)] {
+      cpu.mem_write(0x8000, 2);
+      cpu.mem_write(0x8001, bank);
+
+      assert_eq!(cpu.ppu.mapper.mem_read(0x1005), Some(chr_rom_len + offset));
+
+      cpu.mem_write(0x2006, 0x10);
+      cpu.mem_write(0x2006, 0x05);
+      cpu.mem_write(0x2007, bank);
+
+      assert_eq!(cpu.ppu.chr_ram[offset], bank);
+    }
+
+    // without bit 6 it's a CHR-ROM bank again, and the writes above left CHR-ROM alone
+    cpu.mem_write(0x8001, 0x03);
+
+    assert_eq!(cpu.ppu.mapper.mem_read(0x1005), Some(3 * 1024 + 0x005));
+    assert!(cpu.ppu.chr_rom.iter().all(|byte| *byte == 0));
+  }
+
+  #[test]
+  fn txsrom_nametables_follow_chr_banks() {
+    let mut mapper = test_cartridge(118, 0, 8, 16).mapper;
+
+    // R0 covers the top two nametables, R1 the bottom two
+    for (register, bank) in [(0, 0x80), (1, 0x00)] {
+      mapper.mem_write(0x8000, register);
+      mapper.mem_write(0x8001, bank);
+    }
+
+    assert_eq!(mapper.nametable(0), Nametable::CiramB);
+    assert_eq!(mapper.nametable(1), Nametable::CiramB);
+    assert_eq!(mapper.nametable(2), Nametable::CiramA);
+    assert_eq!(mapper.nametable(3), Nametable::CiramA);
+  }
+
+  #[test]
+  fn counts_filtered_a12_rises() {
+    let mut mapper = test_cartridge(4, 0, 8, 16).mapper;
+
+    mapper.mem_write(0xc000, 2);
+    mapper.mem_write(0xc001, 0);
+    mapper.mem_write(0xe001, 0);
+
+    for line in 0..3 {
+      let m2_cycle = line * 114;
+
+      // background at $0000, then sprites at $1000 with nametable fetches in between
+      mapper.ppu_bus_address(0x0000, m2_cycle);
+      mapper.ppu_bus_address(0x1000, m2_cycle + 86);
+      mapper.ppu_bus_address(0x2000, m2_cycle + 87);
+      mapper.ppu_bus_address(0x1000, m2_cycle + 88);
+
+      assert_eq!(mapper.irq_pending(), line == 2);
+    }
+  }
+
+  #[test]
+  fn four_screen_boards_keep_their_own_nametables() {
+    let mut rom = vec![0x4e, 0x45, 0x53, 0x1a, 4, 4, 0x48, 0x08];
+    rom.resize(16 + 4 * 16_384 + 4 * 8192, 0);
+
+    let mut cartridge = Cartridge::new(rom, None).unwrap();
+
+    cartridge.mapper.mem_write(0xa000, 1);
+
+    assert_eq!(cartridge.vram.len(), 2048);
+    assert_eq!(cartridge.mapper.nametable(1), Nametable::CiramB);
+    assert_eq!(cartridge.mapper.nametable(2), Nametable::CartridgeVram(0));
+    assert_eq!(cartridge.mapper.nametable(3), Nametable::CartridgeVram(1));
+  }
+}
@@ -1,6 +1,6 @@
 use crate::cartridge::{Mirroring, Cartridge};
 
-use super::MapperActions;
+use super::{Mapper, MapperActions, MapperBoard};
 
 const PRG_ROM_BANK_SIZE: usize = 16_384;
 const CHR_RAM_SIZE: usize = 8192;
@@ -15,7 +15,9 @@ impl Uxrom {
 
   pub fn load(cartridge: &mut Cartridge) -> Self {
 
-    cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    if cartridge.chr_ram.len() < CHR_RAM_SIZE {
+      cartridge.chr_ram.resize(CHR_RAM_SIZE, 0);
+    }
 
     let mut prg_rom_page_size: u8 = (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE) as u8;
 
@@ -82,4 +84,12 @@ impl MapperActions for Uxrom {
   fn mirroring(&self) -> Mirroring {
     self.mirroring
   }
- }
\ No newline at end of file
+ }
+
+impl MapperBoard for Uxrom {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(2, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Uxrom::load(cartridge))
+  }
+}
\ No newline at end of file
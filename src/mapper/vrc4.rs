@@ -0,0 +1,240 @@
+use crate::cartridge::{Cartridge, Mirroring};
+
+use super::{Mapper, MapperActions, MapperBoard, BankType};
+use super::vrc_irq::VrcIrq;
+
+const PRG_ROM_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+
+// which CPU address lines each board wires to the chip's two register select pins.
+// submapper 0 means the board is unknown, so both candidate wirings are decoded at once
+// see https://www.nesdev.org/wiki/VRC2_and_VRC4
+fn register_lines(mapper: u16, submapper: u8) -> (u16, u16) {
+  match (mapper, submapper) {
+    // VRC4a, VRC4c
+    (21, 1) => (0x02, 0x04),
+    (21, 2) => (0x40, 0x80),
+    (21, _) => (0x42, 0x84),
+    // VRC2a
+    (22, _) => (0x02, 0x01),
+    // VRC4f and VRC2b, VRC4e
+    (23, 1) | (23, 3) => (0x01, 0x02),
+    (23, 2) => (0x04, 0x08),
+    (23, _) => (0x05, 0x0a),
+    // VRC4b and VRC2c, VRC4d
+    (25, 1) | (25, 3) => (0x02, 0x01),
+    (25, 2) => (0x08, 0x04),
+    _ => (0x0a, 0x05)
+  }
+}
+
+// VRC2 is the VRC4 without the IRQ, PRG swap mode and single screen mirroring
+pub struct Vrc4 {
+  is_vrc2: bool,
+  register_lines: (u16, u16),
+  // VRC2a ignores the low bit of its CHR banks
+  chr_bank_shift: u8,
+  prg_registers: [u8; 2],
+  prg_swap_mode: bool,
+  prg_rom_banks: [usize; 4],
+  prg_page_size: usize,
+  chr_registers: [u16; 8],
+  chr_banks: [usize; 8],
+  chr_page_size: usize,
+  mirroring: Mirroring,
+  microwire_latch: u8,
+  has_prg_ram: bool,
+  irq: VrcIrq
+}
+
+impl Vrc4 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    let mapper = cartridge.header.mapper;
+    let submapper = cartridge.header.submapper;
+
+    let is_vrc2 = mapper == 22 || (matches!(mapper, 23 | 25) && submapper == 3);
+
+    if !is_vrc2 && cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    let mut vrc4 = Self {
+      is_vrc2,
+      register_lines: register_lines(mapper, submapper),
+      chr_bank_shift: if mapper == 22 { 1 } else { 0 },
+      prg_registers: [0; 2],
+      prg_swap_mode: false,
+      prg_rom_banks: [0; 4],
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_registers: [0; 8],
+      chr_banks: [0; 8],
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      mirroring: cartridge.mirroring,
+      microwire_latch: 0,
+      has_prg_ram: !cartridge.prg_ram.is_empty(),
+      irq: VrcIrq::default()
+    };
+
+    vrc4.update_prg_banks();
+
+    vrc4
+  }
+
+  // folds whichever address lines the board uses down to register 0-3
+  fn register(&self, address: u16) -> u16 {
+    let (line0, line1) = self.register_lines;
+
+    let bit0 = (address & line0 != 0) as u16;
+    let bit1 = (address & line1 != 0) as u16;
+
+    bit1 << 1 | bit0
+  }
+
+  fn get_bank_address(&self, bank: usize, page_size: usize, bank_size: usize) -> usize {
+    (bank % page_size) * bank_size
+  }
+
+  fn update_prg_banks(&mut self) {
+    let second_last = self.prg_page_size.saturating_sub(2);
+    let swappable = self.prg_registers[0] as usize;
+
+    let (first, third) = if self.prg_swap_mode { (second_last, swappable) } else { (swappable, second_last) };
+
+    self.prg_rom_banks = [
+      self.get_bank_address(first, self.prg_page_size, PRG_ROM_BANK_SIZE),
+      self.get_bank_address(self.prg_registers[1] as usize, self.prg_page_size, PRG_ROM_BANK_SIZE),
+      self.get_bank_address(third, self.prg_page_size, PRG_ROM_BANK_SIZE),
+      self.get_bank_address(self.prg_page_size - 1, self.prg_page_size, PRG_ROM_BANK_SIZE)
+    ];
+  }
+
+  fn write_chr_register(&mut self, index: usize, high: bool, val: u8) {
+    let register = self.chr_registers[index];
+
+    self.chr_registers[index] = if high {
+      (register & 0x0f) | ((val as u16 & 0x1f) << 4)
+    } else {
+      (register & 0x1f0) | (val as u16 & 0x0f)
+    };
+
+    let bank = (self.chr_registers[index] >> self.chr_bank_shift) as usize;
+
+    self.chr_banks[index] = self.get_bank_address(bank, self.chr_page_size, CHR_BANK_SIZE);
+  }
+
+  fn translate_address(&self, address: u16, bank_type: BankType) -> usize {
+    match bank_type {
+      BankType::Chr => self.chr_banks[(address as usize) / CHR_BANK_SIZE] | (address as usize) & (CHR_BANK_SIZE - 1),
+      BankType::Prg => self.prg_rom_banks[(address - 0x8000) as usize / PRG_ROM_BANK_SIZE] | (address as usize) & (PRG_ROM_BANK_SIZE - 1)
+    }
+  }
+}
+
+impl MapperActions for Vrc4 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.translate_address(address, BankType::Chr)),
+      0x6000..=0x7fff if self.has_prg_ram => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => Some(self.translate_address(address, BankType::Prg)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if (0x6000..=0x7fff).contains(&address) {
+      if self.has_prg_ram {
+        return Some((address - 0x6000) as usize);
+      }
+
+      return None;
+    }
+
+    let register = self.register(address);
+
+    match (address & 0xf000, register) {
+      (0x8000, _) => {
+        self.prg_registers[0] = val & 0x1f;
+        self.update_prg_banks();
+      }
+      (0x9000, 0) | (0x9000, 1) if !self.is_vrc2 => {
+        self.mirroring = match val & 0b11 {
+          0 => Mirroring::Vertical,
+          1 => Mirroring::Horizontal,
+          2 => Mirroring::SingleScreenA,
+          _ => Mirroring::SingleScreenB
+        };
+      }
+      (0x9000, _) if self.is_vrc2 => {
+        self.mirroring = if val & 0b1 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+      }
+      (0x9000, _) => {
+        self.prg_swap_mode = val & 0b10 != 0;
+        self.update_prg_banks();
+      }
+      (0xa000, _) => {
+        self.prg_registers[1] = val & 0x1f;
+        self.update_prg_banks();
+      }
+      (0xb000..=0xe000, _) => {
+        // each pair of registers is the low and high half of one 1K bank
+        let index = (((address & 0xf000) - 0xb000) >> 11) as usize | (register as usize >> 1);
+
+        self.write_chr_register(index, register & 0b1 == 1, val);
+      }
+      (0xf000, 0) if !self.is_vrc2 => self.irq.latch = (self.irq.latch & 0xf0) | (val & 0x0f),
+      (0xf000, 1) if !self.is_vrc2 => self.irq.latch = (self.irq.latch & 0x0f) | (val << 4),
+      (0xf000, 2) if !self.is_vrc2 => self.irq.write_control(val),
+      (0xf000, 3) if !self.is_vrc2 => self.irq.acknowledge(),
+      _ => ()
+    }
+
+    None
+  }
+
+  // VRC2 boards without PRG-RAM have a one bit latch at $6000 that some games use as a copy check
+  fn cpu_read(&mut self, address: u16) -> Option<u8> {
+    match address {
+      0x6000..=0x6fff if self.is_vrc2 && !self.has_prg_ram => Some(0x60 | self.microwire_latch),
+      _ => None
+    }
+  }
+
+  fn cpu_write(&mut self, address: u16, val: u8) -> bool {
+    match address {
+      0x6000..=0x6fff if self.is_vrc2 && !self.has_prg_ram => {
+        self.microwire_latch = val & 0b1;
+
+        true
+      }
+      _ => false
+    }
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    self.irq.tick(cycles);
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq.pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq.pending = val;
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Vrc4 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(21, None), (22, None), (23, None), (25, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Vrc4::load(cartridge))
+  }
+}
@@ -0,0 +1,316 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::cpu::apu::FULL_VOLUME_PULSE;
+
+use super::{Mapper, MapperActions, MapperBoard};
+use super::vrc_irq::VrcIrq;
+
+const PRG_ROM_16K_BANK_SIZE: usize = 16_384;
+const PRG_ROM_8K_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+
+// per step of the channels' volume, so a VRC6 pulse at 15 lines up with a 2A03 pulse at 15
+const OUTPUT_LEVEL: f32 = FULL_VOLUME_PULSE / 15.0;
+
+// see https://www.nesdev.org/wiki/VRC6_audio
+struct Vrc6Pulse {
+  volume: u8,
+  duty: u8,
+  ignore_duty: bool,
+  enabled: bool,
+  period: u16,
+  divider: u16,
+  step: u8
+}
+
+impl Vrc6Pulse {
+  fn new() -> Self {
+    Self {
+      volume: 0,
+      duty: 0,
+      ignore_duty: false,
+      enabled: false,
+      period: 0,
+      divider: 0,
+      step: 15
+    }
+  }
+
+  fn write(&mut self, register: u16, val: u8) {
+    match register {
+      0 => {
+        self.ignore_duty = val & 0x80 != 0;
+        self.duty = (val >> 4) & 0b111;
+        self.volume = val & 0x0f;
+      }
+      1 => self.period = (self.period & 0xf00) | val as u16,
+      _ => {
+        self.period = (self.period & 0xff) | ((val as u16 & 0x0f) << 8);
+        self.enabled = val & 0x80 != 0;
+
+        if !self.enabled {
+          self.step = 15;
+        }
+      }
+    }
+  }
+
+  fn tick(&mut self, shift: u8) {
+    if !self.enabled {
+      return;
+    }
+
+    if self.divider == 0 {
+      self.divider = self.period >> shift;
+      self.step = if self.step == 0 { 15 } else { self.step - 1 };
+    } else {
+      self.divider -= 1;
+    }
+  }
+
+  fn output(&self) -> u8 {
+    if self.enabled && (self.ignore_duty || self.step <= self.duty) {
+      self.volume
+    } else {
+      0
+    }
+  }
+}
+
+struct Vrc6Saw {
+  rate: u8,
+  enabled: bool,
+  period: u16,
+  divider: u16,
+  step: u8,
+  accumulator: u8
+}
+
+impl Vrc6Saw {
+  fn new() -> Self {
+    Self {
+      rate: 0,
+      enabled: false,
+      period: 0,
+      divider: 0,
+      step: 0,
+      accumulator: 0
+    }
+  }
+
+  fn write(&mut self, register: u16, val: u8) {
+    match register {
+      0 => self.rate = val & 0x3f,
+      1 => self.period = (self.period & 0xf00) | val as u16,
+      _ => {
+        self.period = (self.period & 0xff) | ((val as u16 & 0x0f) << 8);
+        self.enabled = val & 0x80 != 0;
+
+        if !self.enabled {
+          self.step = 0;
+          self.accumulator = 0;
+        }
+      }
+    }
+  }
+
+  // the accumulator grows on every other clock and resets after the seventh addition
+  fn tick(&mut self, shift: u8) {
+    if !self.enabled {
+      return;
+    }
+
+    if self.divider > 0 {
+      self.divider -= 1;
+      return;
+    }
+
+    self.divider = self.period >> shift;
+    self.step += 1;
+
+    if self.step == 14 {
+      self.step = 0;
+      self.accumulator = 0;
+    } else if self.step & 0b1 == 0 {
+      self.accumulator = self.accumulator.wrapping_add(self.rate);
+    }
+  }
+
+  fn output(&self) -> u8 {
+    self.accumulator >> 3
+  }
+}
+
+// mapper 26 is the same chip with A0 and A1 swapped.
+// only the CHR layout every released game uses (mode 0, eight 1K banks) is supported
+// see https://www.nesdev.org/wiki/VRC6
+pub struct Vrc6 {
+  swap_lines: bool,
+  prg_rom_banks: [usize; 2],
+  prg_16k_page_size: usize,
+  prg_8k_page_size: usize,
+  chr_banks: [usize; 8],
+  chr_page_size: usize,
+  prg_ram_enabled: bool,
+  mirroring: Mirroring,
+  irq: VrcIrq,
+  audio_halted: bool,
+  frequency_shift: u8,
+  pulse1: Vrc6Pulse,
+  pulse2: Vrc6Pulse,
+  saw: Vrc6Saw
+}
+
+impl Vrc6 {
+  pub fn load(cartridge: &mut Cartridge, swap_lines: bool) -> Self {
+    if cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    Self {
+      swap_lines,
+      prg_rom_banks: [0; 2],
+      prg_16k_page_size: (cartridge.prg_rom.len() / PRG_ROM_16K_BANK_SIZE).max(1),
+      prg_8k_page_size: (cartridge.prg_rom.len() / PRG_ROM_8K_BANK_SIZE).max(1),
+      chr_banks: [0; 8],
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      prg_ram_enabled: false,
+      mirroring: cartridge.mirroring,
+      irq: VrcIrq::default(),
+      audio_halted: false,
+      frequency_shift: 0,
+      pulse1: Vrc6Pulse::new(),
+      pulse2: Vrc6Pulse::new(),
+      saw: Vrc6Saw::new()
+    }
+  }
+
+  fn register(&self, address: u16) -> u16 {
+    let register = address & 0b11;
+
+    if self.swap_lines {
+      (register >> 1) | ((register & 0b1) << 1)
+    } else {
+      register
+    }
+  }
+
+  fn translate_prg_address(&self, address: u16) -> usize {
+    match address {
+      0x8000..=0xbfff => self.prg_rom_banks[0] | (address as usize) & (PRG_ROM_16K_BANK_SIZE - 1),
+      0xc000..=0xdfff => self.prg_rom_banks[1] | (address as usize) & (PRG_ROM_8K_BANK_SIZE - 1),
+      _ => (self.prg_8k_page_size - 1) * PRG_ROM_8K_BANK_SIZE + ((address as usize) & (PRG_ROM_8K_BANK_SIZE - 1))
+    }
+  }
+
+  fn write_banking_mode(&mut self, val: u8) {
+    self.prg_ram_enabled = val & 0x80 != 0;
+
+    self.mirroring = match (val >> 2) & 0b11 {
+      0 => Mirroring::Vertical,
+      1 => Mirroring::Horizontal,
+      2 => Mirroring::SingleScreenA,
+      _ => Mirroring::SingleScreenB
+    };
+  }
+
+  fn write_audio_control(&mut self, val: u8) {
+    self.audio_halted = val & 0b1 != 0;
+
+    self.frequency_shift = if val & 0b100 != 0 {
+      8
+    } else if val & 0b10 != 0 {
+      4
+    } else {
+      0
+    };
+  }
+}
+
+impl MapperActions for Vrc6 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_banks[(address as usize) / CHR_BANK_SIZE] | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x6000..=0x7fff if self.prg_ram_enabled => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => Some(self.translate_prg_address(address)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if (0x6000..=0x7fff).contains(&address) {
+      if self.prg_ram_enabled {
+        return Some((address - 0x6000) as usize);
+      }
+
+      return None;
+    }
+
+    let register = self.register(address);
+
+    match (address & 0xf000, register) {
+      (0x8000, _) => self.prg_rom_banks[0] = ((val & 0x0f) as usize % self.prg_16k_page_size) * PRG_ROM_16K_BANK_SIZE,
+      (0x9000, 3) => self.write_audio_control(val),
+      (0x9000, _) => self.pulse1.write(register, val),
+      (0xa000, 3) => (),
+      (0xa000, _) => self.pulse2.write(register, val),
+      (0xb000, 3) => self.write_banking_mode(val),
+      (0xb000, _) => self.saw.write(register, val),
+      (0xc000, _) => self.prg_rom_banks[1] = ((val & 0x1f) as usize % self.prg_8k_page_size) * PRG_ROM_8K_BANK_SIZE,
+      (0xd000, _) | (0xe000, _) => {
+        let index = (((address & 0xf000) - 0xd000) >> 10) as usize + register as usize;
+
+        self.chr_banks[index] = (val as usize % self.chr_page_size) * CHR_BANK_SIZE;
+      }
+      (0xf000, 0) => self.irq.latch = val,
+      (0xf000, 1) => self.irq.write_control(val),
+      (0xf000, 2) => self.irq.acknowledge(),
+      _ => ()
+    }
+
+    None
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    self.irq.tick(cycles);
+
+    if self.audio_halted {
+      return;
+    }
+
+    for _ in 0..cycles {
+      self.pulse1.tick(self.frequency_shift);
+      self.pulse2.tick(self.frequency_shift);
+      self.saw.tick(self.frequency_shift);
+    }
+  }
+
+  fn audio_output(&self) -> f32 {
+    let output = self.pulse1.output() + self.pulse2.output() + self.saw.output();
+
+    output as f32 * OUTPUT_LEVEL
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq.pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq.pending = val;
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Vrc6 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(24, None), (26, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Vrc6::load(cartridge, cartridge.header.mapper == 26))
+  }
+}
@@ -0,0 +1,175 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::cpu::apu::FULL_VOLUME_PULSE;
+
+use super::{Mapper, MapperActions, MapperBoard};
+use super::opll::Opll;
+use super::vrc_irq::VrcIrq;
+
+const PRG_ROM_BANK_SIZE: usize = 8192;
+const CHR_BANK_SIZE: usize = 1024;
+
+const PRG_RAM_SIZE: usize = 8192;
+
+// Opll::output's carriers run from -1.0 to 1.0, twice the span of a pulse's 0 to full
+const OUTPUT_LEVEL: f32 = FULL_VOLUME_PULSE / 2.0;
+
+// VRC7a (Lagrange Point) selects the second register of each pair with A4, VRC7b with A3.
+// submapper 0 decodes both
+fn register_line(submapper: u8) -> u16 {
+  match submapper {
+    1 => 0x08,
+    2 => 0x10,
+    _ => 0x18
+  }
+}
+
+// see https://www.nesdev.org/wiki/VRC7
+pub struct Vrc7 {
+  register_line: u16,
+  prg_rom_banks: [usize; 3],
+  prg_page_size: usize,
+  chr_banks: [usize; 8],
+  chr_page_size: usize,
+  prg_ram_enabled: bool,
+  mirroring: Mirroring,
+  irq: VrcIrq,
+  audio_silenced: bool,
+  opll: Opll
+}
+
+impl Vrc7 {
+  pub fn load(cartridge: &mut Cartridge) -> Self {
+    if cartridge.prg_ram.len() < PRG_RAM_SIZE {
+      cartridge.prg_ram.resize(PRG_RAM_SIZE, 0);
+    }
+
+    let chr_length = if cartridge.chr_rom.is_empty() { cartridge.chr_ram.len() } else { cartridge.chr_rom.len() };
+
+    Self {
+      register_line: register_line(cartridge.header.submapper),
+      prg_rom_banks: [0; 3],
+      prg_page_size: (cartridge.prg_rom.len() / PRG_ROM_BANK_SIZE).max(1),
+      chr_banks: [0; 8],
+      chr_page_size: (chr_length / CHR_BANK_SIZE).max(1),
+      prg_ram_enabled: false,
+      mirroring: cartridge.mirroring,
+      irq: VrcIrq::default(),
+      audio_silenced: false,
+      opll: Opll::default()
+    }
+  }
+
+  fn translate_prg_address(&self, address: u16) -> usize {
+    let offset = (address as usize) & (PRG_ROM_BANK_SIZE - 1);
+
+    match address {
+      0x8000..=0xdfff => self.prg_rom_banks[((address - 0x8000) as usize) / PRG_ROM_BANK_SIZE] | offset,
+      _ => (self.prg_page_size - 1) * PRG_ROM_BANK_SIZE + offset
+    }
+  }
+
+  fn write_control(&mut self, val: u8) {
+    self.mirroring = match val & 0b11 {
+      0 => Mirroring::Vertical,
+      1 => Mirroring::Horizontal,
+      2 => Mirroring::SingleScreenA,
+      _ => Mirroring::SingleScreenB
+    };
+
+    self.audio_silenced = val & 0x40 != 0;
+    self.prg_ram_enabled = val & 0x80 != 0;
+
+    // silencing also holds the synth in reset
+    if self.audio_silenced {
+      self.opll.reset();
+    }
+  }
+}
+
+impl MapperActions for Vrc7 {
+  fn mem_read(&mut self, address: u16) -> Option<usize> {
+    match address {
+      0x0000..=0x1fff => Some(self.chr_banks[(address as usize) / CHR_BANK_SIZE] | (address as usize) & (CHR_BANK_SIZE - 1)),
+      0x6000..=0x7fff if self.prg_ram_enabled => Some((address - 0x6000) as usize),
+      0x8000..=0xffff => Some(self.translate_prg_address(address)),
+      _ => None
+    }
+  }
+
+  fn mem_write(&mut self, address: u16, val: u8) -> Option<usize> {
+    if (0x6000..=0x7fff).contains(&address) {
+      if self.prg_ram_enabled {
+        return Some((address - 0x6000) as usize);
+      }
+
+      return None;
+    }
+
+    // the audio ports sit at fixed addresses on both board variants
+    if address == 0x9010 {
+      self.opll.select_register(val);
+
+      return None;
+    }
+
+    if address == 0x9030 {
+      if !self.audio_silenced {
+        self.opll.write_data(val);
+      }
+
+      return None;
+    }
+
+    let second = address & self.register_line != 0;
+
+    match (address & 0xf000, second) {
+      (0x8000, false) => self.prg_rom_banks[0] = ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE,
+      (0x8000, true) => self.prg_rom_banks[1] = ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE,
+      (0x9000, false) => self.prg_rom_banks[2] = ((val & 0x3f) as usize % self.prg_page_size) * PRG_ROM_BANK_SIZE,
+      (0xa000..=0xd000, _) => {
+        let index = (((address & 0xf000) - 0xa000) >> 11) as usize + second as usize;
+
+        self.chr_banks[index] = (val as usize % self.chr_page_size) * CHR_BANK_SIZE;
+      }
+      (0xe000, false) => self.write_control(val),
+      (0xe000, true) => self.irq.latch = val,
+      (0xf000, false) => self.irq.write_control(val),
+      (0xf000, true) => self.irq.acknowledge(),
+      _ => ()
+    }
+
+    None
+  }
+
+  fn tick(&mut self, cycles: u16) {
+    self.irq.tick(cycles);
+
+    if !self.audio_silenced {
+      self.opll.tick(cycles);
+    }
+  }
+
+  fn audio_output(&self) -> f32 {
+    self.opll.output() as f32 * OUTPUT_LEVEL
+  }
+
+  fn irq_pending(&self) -> bool {
+    self.irq.pending
+  }
+
+  fn set_irq_pending(&mut self, val: bool) {
+    self.irq.pending = val;
+  }
+
+  fn mirroring(&self) -> Mirroring {
+    self.mirroring
+  }
+}
+
+impl MapperBoard for Vrc7 {
+  const MAPPERS: &'static [(u16, Option<u8>)] = &[(85, None)];
+
+  fn create(cartridge: &mut Cartridge) -> Mapper {
+    Box::new(Vrc7::load(cartridge))
+  }
+}
@@ -0,0 +1,65 @@
+// the IRQ counter shared by Konami's VRC4, VRC6 and VRC7. it counts CPU cycles,
+// either directly or through a prescaler that approximates scanlines.
+// see https://www.nesdev.org/wiki/VRC_IRQ
+const PRESCALER_RELOAD: i16 = 341;
+const PRESCALER_STEP: i16 = 3;
+
+#[derive(Default)]
+pub struct VrcIrq {
+  pub latch: u8,
+  counter: u8,
+  prescaler: i16,
+  enabled: bool,
+  enable_after_ack: bool,
+  cycle_mode: bool,
+  pub pending: bool
+}
+
+impl VrcIrq {
+  pub fn write_control(&mut self, val: u8) {
+    self.enable_after_ack = val & 0b1 != 0;
+    self.enabled = val & 0b10 != 0;
+    self.cycle_mode = val & 0b100 != 0;
+
+    if self.enabled {
+      self.counter = self.latch;
+      self.prescaler = PRESCALER_RELOAD;
+    }
+
+    self.pending = false;
+  }
+
+  pub fn acknowledge(&mut self) {
+    self.pending = false;
+    self.enabled = self.enable_after_ack;
+  }
+
+  pub fn tick(&mut self, cycles: u16) {
+    if !self.enabled {
+      return;
+    }
+
+    for _ in 0..cycles {
+      if self.cycle_mode {
+        self.clock_counter();
+      } else {
+        // 341 / 3 CPU cycles per scanline
+        self.prescaler -= PRESCALER_STEP;
+
+        if self.prescaler <= 0 {
+          self.prescaler += PRESCALER_RELOAD;
+          self.clock_counter();
+        }
+      }
+    }
+  }
+
+  fn clock_counter(&mut self) {
+    if self.counter == 0xff {
+      self.counter = self.latch;
+      self.pending = true;
+    } else {
+      self.counter += 1;
+    }
+  }
+}
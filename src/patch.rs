@@ -0,0 +1,230 @@
+pub mod ips;
+pub mod ups;
+pub mod bps;
+
+use std::error::Error;
+use std::fmt;
+
+use crate::cartridge::checksum;
+
+const IPS_ASCII: &[u8] = b"PATCH";
+const UPS_ASCII: &[u8] = b"UPS1";
+const BPS_ASCII: &[u8] = b"BPS1";
+
+// UPS and BPS both end with the source, target and patch CRC32s
+const CHECKSUM_FOOTER_SIZE: usize = 12;
+
+// the target size comes from the patch itself, so it's capped before anything gets allocated.
+// the biggest NES ROMs out there, OneBus multicarts, are a fraction of this
+const MAX_TARGET_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PatchFormat {
+  Ips,
+  Ups,
+  Bps
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+  UnknownFormat,
+  Truncated,
+  PatchChecksumMismatch { expected: u32, actual: u32 },
+  SourceChecksumMismatch { expected: u32, actual: u32 },
+  TargetChecksumMismatch { expected: u32, actual: u32 },
+  SourceSizeMismatch { expected: usize, actual: usize },
+  OutOfBounds
+}
+
+impl fmt::Display for PatchError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PatchError::UnknownFormat => write!(f, "patch is not in IPS, UPS or BPS format"),
+      PatchError::Truncated => write!(f, "patch is truncated"),
+      PatchError::PatchChecksumMismatch { expected, actual } => write!(f, "patch is corrupt: expected CRC32 {expected:08x}, found {actual:08x}"),
+      PatchError::SourceChecksumMismatch { expected, actual } => write!(f, "patch is for a different ROM: expected CRC32 {expected:08x}, found {actual:08x}"),
+      PatchError::TargetChecksumMismatch { expected, actual } => write!(f, "patched ROM is wrong: expected CRC32 {expected:08x}, found {actual:08x}"),
+      PatchError::SourceSizeMismatch { expected, actual } => write!(f, "patch is for a different ROM: expected {expected} bytes, found {actual}"),
+      PatchError::OutOfBounds => write!(f, "patch reads or writes outside of the ROM")
+    }
+  }
+}
+
+impl Error for PatchError { }
+
+impl PatchFormat {
+  pub fn detect(patch: &[u8]) -> Option<Self> {
+    if patch.starts_with(IPS_ASCII) {
+      Some(PatchFormat::Ips)
+    } else if patch.starts_with(UPS_ASCII) {
+      Some(PatchFormat::Ups)
+    } else if patch.starts_with(BPS_ASCII) {
+      Some(PatchFormat::Bps)
+    } else {
+      None
+    }
+  }
+
+  pub fn extension(&self) -> &'static str {
+    match self {
+      PatchFormat::Ips => "ips",
+      PatchFormat::Ups => "ups",
+      PatchFormat::Bps => "bps"
+    }
+  }
+}
+
+// patches are applied to the whole file, header included, which is how
+// patching tools and patch checksums treat NES ROMs
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+  match PatchFormat::detect(patch) {
+    Some(PatchFormat::Ips) => ips::apply(rom, patch),
+    Some(PatchFormat::Ups) => ups::apply(rom, patch),
+    Some(PatchFormat::Bps) => bps::apply(rom, patch),
+    None => Err(PatchError::UnknownFormat)
+  }
+}
+
+struct PatchReader<'a> {
+  patch: &'a [u8],
+  offset: usize
+}
+
+impl<'a> PatchReader<'a> {
+  fn new(patch: &'a [u8], offset: usize) -> Self {
+    Self {
+      patch,
+      offset
+    }
+  }
+
+  fn read_u8(&mut self) -> Result<u8, PatchError> {
+    let byte = *self.patch.get(self.offset).ok_or(PatchError::Truncated)?;
+
+    self.offset += 1;
+
+    Ok(byte)
+  }
+
+  fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], PatchError> {
+    let end = self.offset.checked_add(length).ok_or(PatchError::Truncated)?;
+    let bytes = self.patch.get(self.offset..end).ok_or(PatchError::Truncated)?;
+
+    self.offset = end;
+
+    Ok(bytes)
+  }
+
+  // UPS and BPS share byuu's variable length integer encoding
+  fn read_number(&mut self) -> Result<usize, PatchError> {
+    let mut value: usize = 0;
+    let mut shift: usize = 1;
+
+    loop {
+      let byte = self.read_u8()?;
+
+      value = value
+        .checked_add((byte & 0x7f) as usize * shift)
+        .ok_or(PatchError::OutOfBounds)?;
+
+      if byte & 0x80 != 0 {
+        break;
+      }
+
+      shift = shift.checked_shl(7).ok_or(PatchError::OutOfBounds)?;
+      value = value.checked_add(shift).ok_or(PatchError::OutOfBounds)?;
+    }
+
+    Ok(value)
+  }
+}
+
+struct ChecksumFooter {
+  source: u32,
+  target: u32
+}
+
+fn read_checksum_footer(patch: &[u8]) -> Result<ChecksumFooter, PatchError> {
+  if patch.len() < CHECKSUM_FOOTER_SIZE {
+    return Err(PatchError::Truncated);
+  }
+
+  let footer = &patch[patch.len() - CHECKSUM_FOOTER_SIZE..];
+  let read = |offset: usize| u32::from_le_bytes([footer[offset], footer[offset + 1], footer[offset + 2], footer[offset + 3]]);
+
+  let patch_checksum = read(8);
+  let actual = checksum::crc32(&patch[..patch.len() - 4]);
+
+  if patch_checksum != actual {
+    return Err(PatchError::PatchChecksumMismatch { expected: patch_checksum, actual });
+  }
+
+  Ok(ChecksumFooter {
+    source: read(0),
+    target: read(4)
+  })
+}
+
+fn check_target_size(target_size: usize) -> Result<(), PatchError> {
+  if target_size > MAX_TARGET_SIZE {
+    return Err(PatchError::OutOfBounds);
+  }
+
+  Ok(())
+}
+
+fn verify_source(rom: &[u8], footer: &ChecksumFooter) -> Result<(), PatchError> {
+  let actual = checksum::crc32(rom);
+
+  if actual != footer.source {
+    return Err(PatchError::SourceChecksumMismatch { expected: footer.source, actual });
+  }
+
+  Ok(())
+}
+
+fn verify_target(target: &[u8], footer: &ChecksumFooter) -> Result<(), PatchError> {
+  let actual = checksum::crc32(target);
+
+  if actual != footer.target {
+    return Err(PatchError::TargetChecksumMismatch { expected: footer.target, actual });
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::{apply, PatchError};
+  use crate::cartridge::checksum;
+
+  // byuu's variable length integer encoding, the inverse of PatchReader::read_number
+  pub(crate) fn number(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    loop {
+      let byte = (value & 0x7f) as u8;
+      value >>= 7;
+
+      if value == 0 {
+        bytes.push(byte | 0x80);
+        return bytes;
+      }
+
+      bytes.push(byte);
+      value -= 1;
+    }
+  }
+
+  pub(crate) fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    patch.extend_from_slice(&checksum::crc32(source).to_le_bytes());
+    patch.extend_from_slice(&checksum::crc32(target).to_le_bytes());
+    patch.extend_from_slice(&checksum::crc32(&patch).to_le_bytes());
+    patch
+  }
+
+  #[test]
+  fn rejects_unknown_formats() {
+    assert_eq!(apply(&[0; 4], b"NOT A PATCH"), Err(PatchError::UnknownFormat));
+  }
+}
@@ -0,0 +1,182 @@
+use super::{PatchError, PatchReader, BPS_ASCII, CHECKSUM_FOOTER_SIZE, read_checksum_footer, check_target_size, verify_source, verify_target};
+
+const SOURCE_READ: usize = 0;
+const TARGET_READ: usize = 1;
+const SOURCE_COPY: usize = 2;
+const TARGET_COPY: usize = 3;
+
+// see https://www.romhacking.net/documents/746/
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+  let footer = read_checksum_footer(patch)?;
+
+  verify_source(rom, &footer)?;
+
+  let mut reader = PatchReader::new(patch, BPS_ASCII.len());
+
+  let source_size = reader.read_number()?;
+  let target_size = reader.read_number()?;
+  let metadata_size = reader.read_number()?;
+
+  if source_size != rom.len() {
+    return Err(PatchError::SourceSizeMismatch { expected: source_size, actual: rom.len() });
+  }
+
+  check_target_size(target_size)?;
+
+  reader.read_bytes(metadata_size)?;
+
+  let mut target: Vec<u8> = vec![0; target_size];
+
+  let actions_end = patch.len() - CHECKSUM_FOOTER_SIZE;
+  let mut output_offset: usize = 0;
+  let mut source_relative_offset: usize = 0;
+  let mut target_relative_offset: usize = 0;
+
+  while reader.offset < actions_end {
+    let data = reader.read_number()?;
+    let command = data & 0b11;
+    let length = (data >> 2) + 1;
+
+    let output_end = output_offset.checked_add(length).ok_or(PatchError::OutOfBounds)?;
+    if output_end > target.len() {
+      return Err(PatchError::OutOfBounds);
+    }
+
+    match command {
+      SOURCE_READ => {
+        let source = rom.get(output_offset..output_end).ok_or(PatchError::OutOfBounds)?;
+
+        target[output_offset..output_end].copy_from_slice(source);
+      }
+      TARGET_READ => {
+        let data = reader.read_bytes(length)?;
+
+        target[output_offset..output_end].copy_from_slice(data);
+      }
+      SOURCE_COPY => {
+        source_relative_offset = relative_offset(source_relative_offset, reader.read_number()?)?;
+
+        let source_end = source_relative_offset.checked_add(length).ok_or(PatchError::OutOfBounds)?;
+        let source = rom.get(source_relative_offset..source_end).ok_or(PatchError::OutOfBounds)?;
+
+        target[output_offset..output_end].copy_from_slice(source);
+
+        source_relative_offset = source_end;
+      }
+      TARGET_COPY => {
+        target_relative_offset = relative_offset(target_relative_offset, reader.read_number()?)?;
+
+        // the copy is allowed to overlap the bytes it's writing, so it has to go one byte at a time
+        for i in 0..length {
+          let byte = *target.get(target_relative_offset + i).ok_or(PatchError::OutOfBounds)?;
+
+          target[output_offset + i] = byte;
+        }
+
+        target_relative_offset += length;
+      }
+      _ => panic!("impossible")
+    }
+
+    output_offset = output_end;
+  }
+
+  verify_target(&target, &footer)?;
+
+  Ok(target)
+}
+
+// relative offsets store their sign in the low bit
+fn relative_offset(offset: usize, data: usize) -> Result<usize, PatchError> {
+  let delta = data >> 1;
+
+  if data & 0b1 == 1 {
+    offset.checked_sub(delta).ok_or(PatchError::OutOfBounds)
+  } else {
+    offset.checked_add(delta).ok_or(PatchError::OutOfBounds)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{apply, SOURCE_READ, TARGET_READ, TARGET_COPY};
+  use crate::patch::PatchError;
+  use crate::patch::tests::{number, with_footer};
+
+  const SOURCE: [u8; 4] = [1, 2, 3, 4];
+
+  fn action(command: usize, length: usize) -> Vec<u8> {
+    number((length - 1) << 2 | command)
+  }
+
+  fn patch(target: &[u8], actions: &[Vec<u8>]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    patch.extend(number(SOURCE.len()));
+    patch.extend(number(target.len()));
+    patch.extend(number(0));
+    patch.extend(actions.concat());
+
+    with_footer(patch, &SOURCE, target)
+  }
+
+  #[test]
+  fn applies_source_and_target_reads() {
+    let target = [1, 9, 3, 4, 5];
+    let patch = patch(&target, &[
+      action(SOURCE_READ, 1),
+      action(TARGET_READ, 1), vec![9],
+      action(SOURCE_READ, 2),
+      action(TARGET_READ, 1), vec![5]
+    ]);
+
+    assert_eq!(apply(&SOURCE, &patch), Ok(target.to_vec()));
+  }
+
+  #[test]
+  fn applies_overlapping_target_copies() {
+    let target = [1, 1, 1, 1];
+    let patch = patch(&target, &[
+      action(SOURCE_READ, 1),
+      action(TARGET_COPY, 3), number(0)
+    ]);
+
+    assert_eq!(apply(&SOURCE, &patch), Ok(target.to_vec()));
+  }
+
+  #[test]
+  fn rejects_a_bad_target_checksum() {
+    let mut patch = patch(&[1, 9, 3, 4, 5], &[
+      action(SOURCE_READ, 1),
+      action(TARGET_READ, 1), vec![8],
+      action(SOURCE_READ, 2),
+      action(TARGET_READ, 1), vec![5]
+    ]);
+
+    // recompute the patch checksum so only the target's is wrong
+    let end = patch.len() - 4;
+    let crc = crate::cartridge::checksum::crc32(&patch[..end]);
+    patch[end..].copy_from_slice(&crc.to_le_bytes());
+
+    assert!(matches!(apply(&SOURCE, &patch), Err(PatchError::TargetChecksumMismatch { .. })));
+  }
+
+  #[test]
+  fn rejects_huge_targets() {
+    let mut patch = b"BPS1".to_vec();
+    patch.extend(number(SOURCE.len()));
+    patch.extend(number(usize::MAX >> 8));
+    patch.extend(number(0));
+
+    let patch = with_footer(patch, &SOURCE, &SOURCE);
+
+    assert_eq!(apply(&SOURCE, &patch), Err(PatchError::OutOfBounds));
+  }
+
+  #[test]
+  fn rejects_a_bad_patch_checksum() {
+    let mut patch = patch(&[1, 2, 3, 4], &[action(SOURCE_READ, 4)]);
+    patch[4] ^= 0x01;
+
+    assert!(matches!(apply(&SOURCE, &patch), Err(PatchError::PatchChecksumMismatch { .. })));
+  }
+}
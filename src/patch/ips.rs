@@ -0,0 +1,126 @@
+use super::{PatchError, PatchReader, IPS_ASCII};
+
+const EOF_MARKER: &[u8] = b"EOF";
+// an offset of $454f46 would read as "EOF", so records can't start there
+const EOF_OFFSET: usize = 0x454f46;
+const MAX_OFFSET: usize = 0xffffff;
+const MAX_RECORD_SIZE: usize = 0xffff;
+
+// see https://zerosoft.zophar.net/ips.php
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+  let mut target = rom.to_vec();
+  let mut reader = PatchReader::new(patch, IPS_ASCII.len());
+
+  loop {
+    let offset_bytes = reader.read_bytes(3)?;
+
+    if offset_bytes == EOF_MARKER {
+      break;
+    }
+
+    let offset = (offset_bytes[0] as usize) << 16 | (offset_bytes[1] as usize) << 8 | offset_bytes[2] as usize;
+    let size = (reader.read_u8()? as usize) << 8 | reader.read_u8()? as usize;
+
+    if size == 0 {
+      // run length encoded record
+      let run_length = (reader.read_u8()? as usize) << 8 | reader.read_u8()? as usize;
+      let value = reader.read_u8()?;
+
+      write(&mut target, offset, &vec![value; run_length]);
+    } else {
+      let data = reader.read_bytes(size)?;
+
+      write(&mut target, offset, data);
+    }
+  }
+
+  // lunar ips extension: three bytes after EOF truncate the file
+  if let Ok(length) = reader.read_bytes(3) {
+    let length = (length[0] as usize) << 16 | (length[1] as usize) << 8 | length[2] as usize;
+
+    target.truncate(length);
+  }
+
+  Ok(target)
+}
+
+fn write(target: &mut Vec<u8>, offset: usize, data: &[u8]) {
+  let end = offset + data.len();
+
+  if target.len() < end {
+    target.resize(end, 0);
+  }
+
+  target[offset..end].copy_from_slice(data);
+}
+
+// builds a patch that turns original into modified. both are expected to be the same size,
+// which is the case for things like disk images where only the contents change
+pub fn create(original: &[u8], modified: &[u8]) -> Vec<u8> {
+  let mut patch = IPS_ASCII.to_vec();
+  let mut offset = 0;
+
+  while offset < modified.len().min(MAX_OFFSET) {
+    if original.get(offset) == Some(&modified[offset]) {
+      offset += 1;
+      continue;
+    }
+
+    let start = if offset == EOF_OFFSET { offset - 1 } else { offset };
+    let mut end = offset;
+
+    while end < modified.len() && end - start < MAX_RECORD_SIZE && original.get(end) != Some(&modified[end]) {
+      end += 1;
+    }
+
+    let size = end - start;
+
+    patch.extend_from_slice(&[(start >> 16) as u8, (start >> 8) as u8, start as u8]);
+    patch.extend_from_slice(&[(size >> 8) as u8, size as u8]);
+    patch.extend_from_slice(&modified[start..end]);
+
+    offset = end;
+  }
+
+  patch.extend_from_slice(EOF_MARKER);
+
+  patch
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{apply, create};
+  use crate::patch::PatchError;
+
+  #[test]
+  fn applies_records_and_runs() {
+    let mut patch = b"PATCH".to_vec();
+    patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xaa, 0xbb]);
+    patch.extend_from_slice(&[0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0x11]);
+    patch.extend_from_slice(b"EOF");
+
+    assert_eq!(apply(&[0; 6], &patch), Ok(vec![0, 0, 0xaa, 0xbb, 0, 0x11, 0x11, 0x11]));
+
+    // lunar ips truncation
+    patch.extend_from_slice(&[0x00, 0x00, 0x04]);
+
+    assert_eq!(apply(&[0; 6], &patch), Ok(vec![0, 0, 0xaa, 0xbb]));
+  }
+
+  #[test]
+  fn rejects_truncated_patches() {
+    assert_eq!(apply(&[0; 6], b"PATCH\x00\x00\x02\x00\x04\xaa"), Err(PatchError::Truncated));
+  }
+
+  #[test]
+  fn creates_patches_that_round_trip() {
+    let original: Vec<u8> = (0..=255).collect();
+    let mut modified = original.clone();
+    modified[3] = 0;
+    modified[200..210].fill(0xff);
+
+    let patch = create(&original, &modified);
+
+    assert_eq!(apply(&original, &patch), Ok(modified));
+  }
+}
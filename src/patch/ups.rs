@@ -0,0 +1,105 @@
+use super::{PatchError, PatchReader, UPS_ASCII, CHECKSUM_FOOTER_SIZE, read_checksum_footer, check_target_size, verify_source, verify_target};
+
+// see http://www.romhacking.net/documents/392/
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+  let footer = read_checksum_footer(patch)?;
+
+  verify_source(rom, &footer)?;
+
+  let mut reader = PatchReader::new(patch, UPS_ASCII.len());
+
+  let source_size = reader.read_number()?;
+  let target_size = reader.read_number()?;
+
+  if source_size != rom.len() {
+    return Err(PatchError::SourceSizeMismatch { expected: source_size, actual: rom.len() });
+  }
+
+  check_target_size(target_size)?;
+
+  let mut target = rom.to_vec();
+  target.resize(target_size, 0);
+
+  let hunks_end = patch.len() - CHECKSUM_FOOTER_SIZE;
+  let mut position: usize = 0;
+
+  while reader.offset < hunks_end {
+    position = position.checked_add(reader.read_number()?).ok_or(PatchError::OutOfBounds)?;
+
+    // each hunk xors bytes into the target until it hits a zero, which also consumes a byte
+    loop {
+      let xor = reader.read_u8()?;
+
+      if position < target.len() {
+        let source = rom.get(position).copied().unwrap_or(0);
+
+        target[position] = source ^ xor;
+      } else if xor != 0 {
+        return Err(PatchError::OutOfBounds);
+      }
+
+      position += 1;
+
+      if xor == 0 {
+        break;
+      }
+    }
+  }
+
+  verify_target(&target, &footer)?;
+
+  Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::apply;
+  use crate::patch::PatchError;
+  use crate::patch::tests::{number, with_footer};
+
+  const SOURCE: [u8; 4] = [1, 2, 3, 4];
+  const TARGET: [u8; 5] = [1, 9, 3, 4, 5];
+
+  fn patch() -> Vec<u8> {
+    let mut patch = b"UPS1".to_vec();
+    patch.extend(number(SOURCE.len()));
+    patch.extend(number(TARGET.len()));
+    // skip a byte, xor one, then skip up to the byte past the end of the source
+    patch.extend(number(1));
+    patch.extend_from_slice(&[2 ^ 9, 0]);
+    patch.extend(number(1));
+    patch.extend_from_slice(&[5, 0]);
+
+    with_footer(patch, &SOURCE, &TARGET)
+  }
+
+  #[test]
+  fn applies_hunks() {
+    assert_eq!(apply(&SOURCE, &patch()), Ok(TARGET.to_vec()));
+  }
+
+  #[test]
+  fn rejects_a_bad_patch_checksum() {
+    let mut patch = patch();
+    let last = patch.len() - 1;
+    patch[last] ^= 0xff;
+
+    assert!(matches!(apply(&SOURCE, &patch), Err(PatchError::PatchChecksumMismatch { .. })));
+  }
+
+  #[test]
+  fn rejects_huge_targets() {
+    let mut patch = b"UPS1".to_vec();
+    patch.extend(number(SOURCE.len()));
+    patch.extend(number(usize::MAX >> 8));
+
+    let patch = with_footer(patch, &SOURCE, &TARGET);
+
+    assert_eq!(apply(&SOURCE, &patch), Err(PatchError::OutOfBounds));
+  }
+
+  #[test]
+  fn rejects_the_wrong_source() {
+    assert!(matches!(apply(&[1, 2, 3, 5], &patch()), Err(PatchError::SourceChecksumMismatch { .. })));
+  }
+}
@@ -0,0 +1,182 @@
+use crate::cartridge::{Cartridge, CartridgeError, nsf::{self, NsfFile, TrackInfo}};
+use crate::cpu::{CPU, CpuFlags};
+use crate::cpu::apu::SAMPLE_RATE;
+
+const CPU_CLOCK_RATE: f64 = 1_789_773.0;
+
+// INIT and PLAY are called like subroutines that return here. nothing is ever
+// mapped at this address, the player just watches the program counter for it
+const RETURN_ADDRESS: u16 = 0x4100;
+
+const STACK_START: u8 = 0xfd;
+
+// plays NSF and NSFe rips without a PPU driven game, see https://www.nesdev.org/wiki/NSF#Initializing_a_tune
+pub struct NsfPlayer {
+  pub cpu: CPU,
+  nsf: NsfFile,
+  track: u8,
+  in_routine: bool,
+  cycles_per_play: f64,
+  cycles_until_play: f64,
+  samples_played: usize
+}
+
+impl NsfPlayer {
+  pub fn new(file: &[u8]) -> Result<Self, CartridgeError> {
+    let nsf = nsf::parse(file)?;
+
+    let mut cpu = CPU::new();
+
+    cpu.load_game(Cartridge::new_nsf(&nsf));
+
+    let mut player = Self {
+      cpu,
+      cycles_per_play: nsf.play_speed() as f64 * CPU_CLOCK_RATE / 1_000_000.0,
+      cycles_until_play: 0.0,
+      track: nsf.starting_song,
+      in_routine: false,
+      samples_played: 0,
+      nsf
+    };
+
+    player.select_track(player.track);
+
+    Ok(player)
+  }
+
+  pub fn nsf(&self) -> &NsfFile {
+    &self.nsf
+  }
+
+  pub fn title(&self) -> &str {
+    &self.nsf.title
+  }
+
+  pub fn artist(&self) -> &str {
+    &self.nsf.artist
+  }
+
+  pub fn copyright(&self) -> &str {
+    &self.nsf.copyright
+  }
+
+  pub fn track_count(&self) -> u8 {
+    self.nsf.total_songs
+  }
+
+  pub fn current_track(&self) -> u8 {
+    self.track
+  }
+
+  pub fn track_info(&self, track: u8) -> Option<&TrackInfo> {
+    self.nsf.tracks.get(track as usize)
+  }
+
+  pub fn elapsed_ms(&self) -> u64 {
+    self.samples_played as u64 * 1000 / SAMPLE_RATE as u64
+  }
+
+  // only known for NSFe rips (and NSF2 files with metadata) that list track times
+  pub fn is_track_finished(&self) -> bool {
+    match self.track_info(self.track) {
+      Some(TrackInfo { duration_ms: Some(duration), fade_ms, .. }) => {
+        self.elapsed_ms() >= *duration as u64 + fade_ms.unwrap_or(0) as u64
+      }
+      _ => false
+    }
+  }
+
+  pub fn select_track(&mut self, track: u8) {
+    self.track = track % self.nsf.total_songs.max(1);
+
+    for address in 0x0000..0x0800 {
+      self.cpu.mem_write(address, 0);
+    }
+    for address in 0x6000..0x8000 {
+      self.cpu.mem_write(address, 0);
+    }
+
+    for address in 0x4000..0x4014 {
+      self.cpu.mem_write(address, 0);
+    }
+    self.cpu.mem_write(0x4015, 0);
+    self.cpu.mem_write(0x4015, 0x0f);
+    self.cpu.mem_write(0x4017, 0x40);
+
+    if self.nsf.is_bankswitched() {
+      for (index, bank) in self.nsf.bankswitch.into_iter().enumerate() {
+        self.cpu.mem_write(0x5ff8 + index as u16, bank);
+      }
+    }
+
+    self.cpu.apu.buffer_index = 0;
+    self.cpu.registers.sp = STACK_START;
+    self.cpu.registers.a = self.track;
+    self.cpu.registers.x = if self.nsf.is_pal { 1 } else { 0 };
+
+    self.call(self.nsf.init_address);
+
+    self.cycles_until_play = self.cycles_per_play;
+    self.samples_played = 0;
+  }
+
+  fn call(&mut self, address: u16) {
+    self.cpu.push_to_stack_u16(RETURN_ADDRESS - 1);
+    self.cpu.registers.pc = address;
+    self.cpu.registers.p.insert(CpuFlags::INTERRUPT_DISABLE);
+
+    self.in_routine = true;
+  }
+
+  fn step(&mut self) {
+    let cycles = if self.in_routine {
+      if self.cpu.registers.pc == RETURN_ADDRESS {
+        self.in_routine = false;
+
+        return;
+      }
+
+      self.cpu.tick()
+    } else {
+      self.cpu.cycle(1);
+
+      1
+    };
+
+    self.cycles_until_play -= cycles as f64;
+
+    if self.cycles_until_play <= 0.0 {
+      self.cycles_until_play += self.cycles_per_play;
+
+      // a PLAY that runs long just misses the next call, like most hardware players do
+      if !self.in_routine {
+        self.call(self.nsf.play_address);
+      }
+    }
+  }
+
+  // runs the tune until the buffer is full of samples at apu::SAMPLE_RATE
+  pub fn render(&mut self, samples: &mut [f32]) {
+    let mut written = 0;
+
+    while written < samples.len() {
+      let available = self.cpu.apu.buffer_index;
+
+      if available == 0 {
+        self.step();
+        continue;
+      }
+
+      let count = available.min(samples.len() - written);
+
+      samples[written..written + count].copy_from_slice(&self.cpu.apu.audio_samples[..count]);
+
+      self.cpu.apu.audio_samples.copy_within(count..available, 0);
+      self.cpu.apu.buffer_index -= count;
+
+      written += count;
+    }
+
+    self.samples_played += samples.len();
+  }
+}
@@ -8,6 +8,7 @@ extern crate nes_emulator;
 use nes_emulator::cpu::CPU;
 use nes_emulator::cpu::ppu::CYCLES_PER_FRAME;
 use nes_emulator::cartridge::Cartridge;
+use nes_emulator::mapper::MapperActions;
 
 #[wasm_bindgen]
 pub struct WasmEmulator {
@@ -87,6 +88,10 @@ impl WasmEmulator {
     self.cpu.prg_ram.len()
   }
 
+  pub fn has_battery(&self) -> bool {
+    self.cpu.has_battery
+  }
+
   pub fn prg_save(&self) -> bool {
     self.cpu.prg_save
   }
@@ -96,7 +101,24 @@ impl WasmEmulator {
   }
 
   pub fn load_prg_ram(&mut self, ram: &[u8]) {
-    self.cpu.prg_ram = ram.to_vec();
+    let length = ram.len().min(self.cpu.prg_ram.len());
+
+    self.cpu.prg_ram[..length].copy_from_slice(&ram[..length]);
+  }
+
+  // everything a save file holds, including state the mapper keeps on its own like FDS disk writes
+  pub fn save_data(&mut self) -> Vec<u8> {
+    self.cpu.prg_save = false;
+
+    self.cpu.save_data()
+  }
+
+  pub fn load_save_data(&mut self, save: &[u8]) {
+    self.cpu.load_save_data(save);
+  }
+
+  pub fn save_pending(&self) -> bool {
+    self.cpu.prg_save || self.cpu.ppu.mapper.save_pending()
   }
 
   pub fn update_buffer(&mut self, buffer: &mut [f32]) {
@@ -129,9 +151,50 @@ impl WasmEmulator {
     self.cpu.ppu.picture.data.as_ptr()
   }
 
-  pub fn load(&mut self, rom: &[u8]) {
-    let cartridge = Cartridge::new(rom.to_vec(), None);
-    self.cpu.load_game(cartridge);
+  pub fn load(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+    match Cartridge::new(rom.to_vec(), None) {
+      Ok(cartridge) => {
+        self.cpu.load_game(cartridge);
+
+        Ok(())
+      }
+      Err(error) => {
+        console_log!("could not load rom: {}", error);
+
+        Err(JsValue::from_str(&error.to_string()))
+      }
+    }
+  }
+
+  pub fn load_fds(&mut self, image: &[u8], bios: &[u8]) -> Result<(), JsValue> {
+    match Cartridge::new_fds(image.to_vec(), bios.to_vec(), None) {
+      Ok(cartridge) => {
+        self.cpu.load_game(cartridge);
+
+        Ok(())
+      }
+      Err(error) => {
+        console_log!("could not load disk: {}", error);
+
+        Err(JsValue::from_str(&error.to_string()))
+      }
+    }
+  }
+
+  pub fn disk_sides(&self) -> usize {
+    self.cpu.ppu.mapper.disk_sides()
+  }
+
+  pub fn insert_disk(&mut self, side: usize) {
+    self.cpu.ppu.mapper.insert_disk(side);
+  }
+
+  pub fn eject_disk(&mut self) {
+    self.cpu.ppu.mapper.eject_disk();
+  }
+
+  pub fn reset(&mut self) {
+    self.cpu.reset();
   }
 
   pub fn update_input(&mut self, button_event: ButtonEvent, is_pressed: bool) {